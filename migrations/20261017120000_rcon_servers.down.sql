@@ -0,0 +1 @@
+DROP TABLE IF EXISTS rcon_servers;
\ No newline at end of file
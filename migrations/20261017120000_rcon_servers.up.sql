@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS rcon_servers (
+    ID INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    host TEXT NOT NULL,
+    port INTEGER NOT NULL,
+    password TEXT NOT NULL,
+    UNIQUE(name)
+);
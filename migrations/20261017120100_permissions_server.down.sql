@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS users_permissions_global (
+    ID INTEGER PRIMARY KEY,
+    user_id INTEGER,
+    command TEXT NOT NULL,
+    FOREIGN KEY(user_id) REFERENCES rcon_users(ID),
+    UNIQUE(command, user_id)
+);
+INSERT OR IGNORE INTO users_permissions_global(ID, user_id, command)
+    SELECT ID, user_id, command FROM users_permissions;
+DROP TABLE users_permissions;
+ALTER TABLE users_permissions_global RENAME TO users_permissions;
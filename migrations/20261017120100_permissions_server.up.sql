@@ -0,0 +1,13 @@
+-- '*' grants the command on every server
+CREATE TABLE IF NOT EXISTS users_permissions_scoped (
+    ID INTEGER PRIMARY KEY,
+    user_id INTEGER,
+    command TEXT NOT NULL,
+    server TEXT NOT NULL DEFAULT '*',
+    FOREIGN KEY(user_id) REFERENCES rcon_users(ID),
+    UNIQUE(command, user_id, server)
+);
+INSERT INTO users_permissions_scoped(ID, user_id, command)
+    SELECT ID, user_id, command FROM users_permissions;
+DROP TABLE users_permissions;
+ALTER TABLE users_permissions_scoped RENAME TO users_permissions;
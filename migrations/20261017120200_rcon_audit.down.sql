@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS rcon_audit_created_at;
+DROP TABLE IF EXISTS rcon_audit;
\ No newline at end of file
@@ -0,0 +1,17 @@
+CREATE TABLE IF NOT EXISTS rcon_audit (
+    ID INTEGER PRIMARY KEY,
+    -- unix timestamp in seconds
+    created_at INTEGER NOT NULL,
+    -- NULL when the caller wasn't logged in
+    user_nick TEXT,
+    source_ip TEXT,
+    server TEXT NOT NULL,
+    command TEXT NOT NULL,
+    -- JSON array with the command arguments
+    args TEXT NOT NULL,
+    allowed INTEGER NOT NULL,
+    response TEXT,
+    error TEXT,
+    latency_ms INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS rcon_audit_created_at ON rcon_audit(created_at);
@@ -0,0 +1,24 @@
+CREATE TABLE IF NOT EXISTS roles (
+    ID INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    UNIQUE(name)
+);
+
+CREATE TABLE IF NOT EXISTS role_permissions (
+    ID INTEGER PRIMARY KEY,
+    role_id INTEGER NOT NULL,
+    command TEXT NOT NULL,
+    -- '*' grants the command on every server
+    server TEXT NOT NULL DEFAULT '*',
+    FOREIGN KEY(role_id) REFERENCES roles(ID),
+    UNIQUE(command, role_id, server)
+);
+
+CREATE TABLE IF NOT EXISTS users_roles (
+    ID INTEGER PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    role_id INTEGER NOT NULL,
+    FOREIGN KEY(user_id) REFERENCES rcon_users(ID),
+    FOREIGN KEY(role_id) REFERENCES roles(ID),
+    UNIQUE(user_id, role_id)
+);
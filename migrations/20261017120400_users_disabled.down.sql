@@ -0,0 +1 @@
+ALTER TABLE rcon_users DROP COLUMN disabled;
\ No newline at end of file
@@ -0,0 +1,2 @@
+-- disabled users can't log in and lose their sessions
+ALTER TABLE rcon_users ADD COLUMN disabled INTEGER NOT NULL DEFAULT 0;
\ No newline at end of file
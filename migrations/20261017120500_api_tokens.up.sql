@@ -0,0 +1,18 @@
+CREATE TABLE IF NOT EXISTS api_tokens (
+    ID INTEGER PRIMARY KEY,
+    -- `jti` claim of the token
+    token_id TEXT NOT NULL,
+    user_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    -- hex SHA-256 of the whole token, the token itself is never stored
+    token_hash TEXT NOT NULL,
+    -- JSON array with the permission patterns allowed to the token
+    scopes TEXT NOT NULL,
+    -- unix timestamps in seconds
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL,
+    revoked INTEGER NOT NULL DEFAULT 0,
+    FOREIGN KEY(user_id) REFERENCES rcon_users(ID),
+    UNIQUE(token_id),
+    UNIQUE(token_hash)
+);
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS sessions (
+    ID INTEGER PRIMARY KEY,
+    -- hex SHA-256 of the session cookie, the key itself is never stored
+    session_hash TEXT NOT NULL,
+    -- JSON object with the session state
+    state TEXT NOT NULL,
+    -- logged user of the session, NULL before the login
+    user_nick TEXT,
+    -- unix timestamps in seconds
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL,
+    UNIQUE(session_hash)
+);
+CREATE INDEX IF NOT EXISTS sessions_user_nick ON sessions(user_nick);
@@ -0,0 +1 @@
+ALTER TABLE rcon_servers DROP COLUMN query_port;
\ No newline at end of file
@@ -0,0 +1,2 @@
+-- port of the Query protocol, 25565 when missing like query.port of server.properties
+ALTER TABLE rcon_servers ADD COLUMN query_port INTEGER;
\ No newline at end of file
@@ -0,0 +1,2 @@
+-- game port answering the Server List Ping, 25565 when missing
+ALTER TABLE rcon_servers ADD COLUMN game_port INTEGER;
\ No newline at end of file
@@ -0,0 +1,4 @@
+UPDATE api_tokens SET scopes = (
+    SELECT json_group_array(json_extract(value, '$.command'))
+    FROM json_each(api_tokens.scopes)
+);
@@ -0,0 +1,5 @@
+-- scopes become {"command": ..., "server": ...} objects, the old ones were valid everywhere
+UPDATE api_tokens SET scopes = (
+    SELECT json_group_array(json_object('command', value, 'server', '*'))
+    FROM json_each(api_tokens.scopes)
+);
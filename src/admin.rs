@@ -0,0 +1,173 @@
+//! `user` and `perm` subcommands, they work straight on the database so access can be
+//! bootstrapped or recovered without the web server.
+
+use std::sync::Arc;
+
+use clap::{arg, ArgMatches, Command};
+use sqlx::SqlitePool;
+
+use crate::{
+    error::{CrateResult, Error},
+    password::PasswordManager,
+    session::SqliteSessionStore,
+    user::{UserManager, ALL_SERVERS},
+};
+
+pub fn user_command() -> Command {
+    let secret_key = || {
+        arg!(--secret_key <SECRET_KEY> "same secret key of the server, to hash passwords")
+            .env("SECRET_KEY")
+            .required(true)
+    };
+
+    Command::new("user")
+        .about("manage the users of the web server")
+        .subcommand(
+            Command::new("add")
+                .about("create a user")
+                .arg(arg!(<NICK> "game nick of the user"))
+                .arg(arg!(--password <PASSWORD> "asked on the terminal when missing"))
+                .arg(secret_key())
+        )
+        .subcommand(Command::new("list").about("list the users"))
+        .subcommand(
+            Command::new("delete")
+                .about("delete a user with its permissions, tokens and sessions")
+                .arg(arg!(<NICK> "game nick of the user"))
+        )
+        .subcommand(
+            Command::new("passwd")
+                .about("change the password of a user and close its sessions")
+                .arg(arg!(<NICK> "game nick of the user"))
+                .arg(arg!(--password <PASSWORD> "asked on the terminal when missing"))
+                .arg(secret_key())
+        )
+        .subcommand_required(true)
+}
+
+pub fn perm_command() -> Command {
+    let server = || arg!(--server <SERVER> "server of the permission, every server when missing");
+
+    Command::new("perm")
+        .about("manage the permissions of the users")
+        .subcommand(
+            Command::new("grant")
+                .about("grant command patterns to a user")
+                .arg(arg!(<NICK> "game nick of the user"))
+                .arg(arg!(<PERMISSION> ... "command patterns, like \"tp {self} *\""))
+                .arg(server())
+        )
+        .subcommand(
+            Command::new("revoke")
+                .about("revoke a command pattern granted to a user")
+                .arg(arg!(<NICK> "game nick of the user"))
+                .arg(arg!(<PERMISSION> "command pattern"))
+                .arg(server())
+        )
+        .subcommand(
+            Command::new("list")
+                .about("list the permissions of a user, including the ones from roles")
+                .arg(arg!(<NICK> "game nick of the user"))
+        )
+        .subcommand_required(true)
+}
+
+fn nick(matches: &ArgMatches) -> String {
+    matches.get_one::<String>("NICK").expect("required").clone()
+}
+
+fn server(matches: &ArgMatches) -> String {
+    matches
+        .get_one::<String>("server")
+        .cloned()
+        .unwrap_or_else(|| ALL_SERVERS.to_string())
+}
+
+/// From `--password` or asked without echo
+fn password(matches: &ArgMatches) -> CrateResult<String> {
+    if let Some(password) = matches.get_one::<String>("password") {
+        return Ok(password.clone());
+    }
+
+    let password = rpassword::prompt_password("password: ").map_err(Error::server_error)?;
+    let confirm = rpassword::prompt_password("confirm password: ").map_err(Error::server_error)?;
+    if password != confirm {
+        return Err(Error::cant_create_user("passwords don't match"));
+    }
+    Ok(password)
+}
+
+/// Hashes the password with the `--secret_key` of the subcommand
+fn password_hash(matches: &ArgMatches, pool: &Arc<SqlitePool>) -> CrateResult<String> {
+    let secret_key = matches.get_one::<String>("secret_key").expect("required");
+
+    PasswordManager::new(Arc::clone(pool), Arc::new(secret_key.clone()))
+        .hash_password(password(matches)?)
+}
+
+pub async fn run_user(matches: &ArgMatches, pool: Arc<SqlitePool>) -> CrateResult<()> {
+    let user_manager = UserManager::new(Arc::clone(&pool));
+    let sessions = SqliteSessionStore::new(Arc::clone(&pool));
+
+    match matches.subcommand() {
+        Some(("add", matches)) => {
+            let hash = password_hash(matches, &pool)?;
+            user_manager.new_user(nick(matches), hash).await
+        },
+        Some(("list", _)) => {
+            for user in user_manager.list_users().await? {
+                if user.disabled {
+                    println!("{} (disabled)", user.nick);
+                } else {
+                    println!("{}", user.nick);
+                }
+            }
+            Ok(())
+        },
+        Some(("delete", matches)) => {
+            let nick = nick(matches);
+            user_manager.delete_user(nick.clone()).await?;
+            sessions.delete_user_sessions(&nick).await
+        },
+        Some(("passwd", matches)) => {
+            let nick = nick(matches);
+            let hash = password_hash(matches, &pool)?;
+            user_manager.set_password(nick.clone(), hash).await?;
+            sessions.delete_user_sessions(&nick).await
+        },
+        _ => unreachable!("subcommand is required"),
+    }
+}
+
+pub async fn run_perm(matches: &ArgMatches, pool: Arc<SqlitePool>) -> CrateResult<()> {
+    let user_manager = UserManager::new(pool);
+
+    match matches.subcommand() {
+        Some(("grant", matches)) => {
+            // the insert would store a grant without user, disabled users can get grants
+            if let Err(err @ Error::UserNotFound { .. }) =
+                user_manager.ensure_active(&nick(matches)).await
+            {
+                return Err(err);
+            }
+
+            let permissions = matches
+                .get_many::<String>("PERMISSION")
+                .expect("required")
+                .cloned()
+                .collect();
+            user_manager.add_user_permissions(nick(matches), server(matches), permissions).await
+        },
+        Some(("revoke", matches)) => {
+            let permission = matches.get_one::<String>("PERMISSION").expect("required").clone();
+            user_manager.revoke_user_permission(nick(matches), server(matches), permission).await
+        },
+        Some(("list", matches)) => {
+            for grant in user_manager.grants(&nick(matches)).await? {
+                println!("{}\t{}", grant.server, grant.command);
+            }
+            Ok(())
+        },
+        _ => unreachable!("subcommand is required"),
+    }
+}
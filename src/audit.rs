@@ -0,0 +1,294 @@
+use std::{
+    sync::Arc,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Json, QueryBuilder, Sqlite, SqlitePool};
+
+use crate::{
+    clock::unix_now,
+    error::{CrateResult, Error},
+    rcon::RconResponse,
+};
+
+/// One pass through the RCON command handler
+pub(crate) struct AuditRecord {
+    pub user_nick: Option<String>,
+    pub source_ip: Option<String>,
+    pub server: String,
+    pub command: String,
+    pub args: Vec<String>,
+    /// Set once the permission check passes
+    pub allowed: bool,
+    pub response: Option<String>,
+    pub error: Option<String>,
+    pub latency: Duration,
+}
+
+impl AuditRecord {
+    pub(crate) fn new(
+        user_nick: Option<String>,
+        source_ip: Option<String>,
+        server: &str,
+        command: &str,
+        args: &[String],
+    ) -> Self {
+        Self {
+            user_nick,
+            source_ip,
+            server: server.to_string(),
+            command: command.to_string(),
+            args: args.to_vec(),
+            allowed: false,
+            response: None,
+            error: None,
+            latency: Duration::ZERO,
+        }
+    }
+
+    /// Keeps the outcome of the call and how long it took
+    pub(crate) fn finish(&mut self, result: &CrateResult<RconResponse>, latency: Duration) {
+        match result {
+            Ok(resp) => self.response = Some(resp.output.clone()),
+            Err(err) => self.error = Some(err.to_string()),
+        }
+        self.latency = latency;
+    }
+}
+
+/// Stored row of the `rcon_audit` table
+#[derive(Serialize, sqlx::FromRow)]
+pub(crate) struct AuditEntry {
+    pub id: i64,
+    pub created_at: i64,
+    pub user_nick: Option<String>,
+    pub source_ip: Option<String>,
+    pub server: String,
+    pub command: String,
+    pub args: Json<Vec<String>>,
+    pub allowed: bool,
+    pub response: Option<String>,
+    pub error: Option<String>,
+    pub latency_ms: i64,
+}
+
+const CSV_HEADER: &str =
+    "id,created_at,user_nick,source_ip,server,command,args,allowed,response,error,latency_ms";
+
+/// Quotes the field when it has separators, quotes or line breaks
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+impl AuditEntry {
+    fn csv_row(&self) -> String {
+        let optional = |field: &Option<String>| csv_field(field.as_deref().unwrap_or_default());
+        [
+            self.id.to_string(),
+            self.created_at.to_string(),
+            optional(&self.user_nick),
+            optional(&self.source_ip),
+            csv_field(&self.server),
+            csv_field(&self.command),
+            csv_field(&self.args.join(" ")),
+            self.allowed.to_string(),
+            optional(&self.response),
+            optional(&self.error),
+            self.latency_ms.to_string(),
+        ].join(",")
+    }
+}
+
+/// Filters of the audit log, all of them are optional
+#[derive(Default, Deserialize)]
+pub(crate) struct AuditFilter {
+    pub nick: Option<String>,
+    pub command: Option<String>,
+    /// Unix timestamp in seconds, inclusive
+    pub from: Option<i64>,
+    /// Unix timestamp in seconds, exclusive
+    pub to: Option<i64>,
+    pub allowed: Option<bool>,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<i64>,
+    pub limit: Option<u32>,
+}
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 1000;
+
+/// Entries from the newest to the oldest
+#[derive(Serialize)]
+pub(crate) struct AuditPage {
+    pub entries: Vec<AuditEntry>,
+    /// Cursor of the next page, `None` on the last one
+    pub next_cursor: Option<i64>,
+}
+
+impl AuditPage {
+    pub(crate) fn to_csv(&self) -> String {
+        let mut csv = String::from(CSV_HEADER);
+        csv.push('\n');
+        for entry in &self.entries {
+            csv.push_str(&entry.csv_row());
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+/// Stores the RCON calls in the `rcon_audit` table
+pub(crate) struct AuditLog {
+    pool: Arc<SqlitePool>,
+}
+
+impl AuditLog {
+    pub(crate) fn new(pool: Arc<SqlitePool>) -> Self {
+        Self { pool }
+    }
+
+    pub(crate) async fn record(&self, record: &AuditRecord) -> CrateResult<()> {
+        let created_at = unix_now()?;
+        let args = serde_json::to_string(&record.args).map_err(Error::server_error)?;
+
+        sqlx::query("
+            INSERT INTO rcon_audit(
+                created_at, user_nick, source_ip, server, command,
+                args, allowed, response, error, latency_ms
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10);
+            ")
+            .bind(created_at)
+            .bind(&record.user_nick)
+            .bind(&record.source_ip)
+            .bind(&record.server)
+            .bind(&record.command)
+            .bind(args)
+            .bind(record.allowed)
+            .bind(&record.response)
+            .bind(&record.error)
+            .bind(record.latency.as_millis() as i64)
+            .execute(Arc::as_ref(&self.pool))
+            .await
+            .map_err(Error::server_error)?;
+
+        Ok(())
+    }
+
+    pub(crate) async fn query(&self, filter: &AuditFilter) -> CrateResult<AuditPage> {
+        let limit = filter.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+        let mut query: QueryBuilder<Sqlite> = QueryBuilder::new("
+            SELECT
+                ID AS id, created_at, user_nick, source_ip, server, command,
+                args, allowed, response, error, latency_ms
+            FROM rcon_audit WHERE 1 = 1");
+
+        if let Some(nick) = &filter.nick {
+            query.push(" AND user_nick = ").push_bind(nick);
+        }
+        if let Some(command) = &filter.command {
+            query.push(" AND command = ").push_bind(command);
+        }
+        if let Some(from) = filter.from {
+            query.push(" AND created_at >= ").push_bind(from);
+        }
+        if let Some(to) = filter.to {
+            query.push(" AND created_at < ").push_bind(to);
+        }
+        if let Some(allowed) = filter.allowed {
+            query.push(" AND allowed = ").push_bind(allowed);
+        }
+        if let Some(cursor) = filter.cursor {
+            query.push(" AND ID < ").push_bind(cursor);
+        }
+        // one more row tells if there is a next page
+        query.push(" ORDER BY ID DESC LIMIT ").push_bind(limit as i64 + 1);
+
+        let mut entries: Vec<AuditEntry> = query
+            .build_query_as()
+            .fetch_all(Arc::as_ref(&self.pool))
+            .await
+            .map_err(Error::server_error)?;
+
+        let next_cursor = if entries.len() > limit as usize {
+            entries.truncate(limit as usize);
+            entries.last().map(|entry| entry.id)
+        } else {
+            None
+        };
+
+        Ok(AuditPage { entries, next_cursor })
+    }
+}
+
+
+#[cfg(test)]
+mod audit_test {
+    use super::*;
+
+    #[tokio::test]
+    async fn record_denied_call() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        let audit = AuditLog::new(Arc::new(pool.clone()));
+
+        let mut record = AuditRecord::new(
+            Some("steve".to_string()),
+            Some("127.0.0.1".to_string()),
+            "default",
+            "op",
+            &["steve".to_string()],
+        );
+        record.finish(&Err(Error::dont_have_permission("steve")), Duration::from_millis(3));
+        audit.record(&record).await.unwrap();
+
+        let row: (String, String, bool, Option<String>) = sqlx::query_as(
+            "SELECT user_nick, args, allowed, error FROM rcon_audit")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(row.0, "steve");
+        assert_eq!(row.1, r#"["steve"]"#);
+        assert!(!row.2);
+        assert!(row.3.is_some());
+    }
+
+    #[tokio::test]
+    async fn query_pages_with_filters() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        let audit = AuditLog::new(Arc::new(pool));
+
+        let calls = [("steve", "op"), ("alex", "say"), ("steve", "say"), ("steve", "op")];
+        for (nick, command) in calls {
+            let mut record = AuditRecord::new(
+                Some(nick.to_string()), None, "default", command, &[]);
+            record.allowed = true;
+            audit.record(&record).await.unwrap();
+        }
+
+        let filter = AuditFilter {
+            nick: Some("steve".to_string()),
+            limit: Some(2),
+            ..Default::default()
+        };
+        let first = audit.query(&filter).await.unwrap();
+        let second = audit
+            .query(&AuditFilter { cursor: first.next_cursor, ..filter })
+            .await
+            .unwrap();
+
+        assert_eq!(first.entries.iter().map(|e| e.id).collect::<Vec<_>>(), vec![4, 3]);
+        assert_eq!(second.entries.iter().map(|e| e.id).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(second.next_cursor, None);
+        assert_eq!(
+            second.to_csv().lines().nth(1).unwrap().split(',').nth(5),
+            Some("op"),
+        );
+    }
+}
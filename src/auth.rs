@@ -0,0 +1,103 @@
+use std::{future::Future, pin::Pin};
+
+use actix_identity::Identity;
+use actix_web::{dev::Payload, http::header::AUTHORIZATION, web, FromRequest, HttpRequest};
+
+use crate::{
+    error::{CrateResult, Error},
+    permission,
+    token::TokenManager,
+    user::{Grant, UserManager, ALL_SERVERS},
+};
+
+/// Authenticated user of a request, from the session cookie or from an
+/// `Authorization: Bearer` token. Deleted and disabled users are rejected and their
+/// sessions closed.
+#[derive(Clone)]
+pub(crate) struct Caller {
+    pub nick: String,
+    /// Permission patterns allowed to the bearer token, `None` for sessions
+    scopes: Option<Vec<Grant>>,
+}
+
+impl Caller {
+    pub(crate) fn is_token(&self) -> bool {
+        self.scopes.is_some()
+    }
+
+    fn scope_allows(&self, server: &str, allowed: impl Fn(&str) -> bool) -> CrateResult<()> {
+        let allows = |scope: &Grant| scope.applies_to(server) && allowed(&scope.command);
+        match &self.scopes {
+            Some(scopes) if !scopes.iter().any(allows) => {
+                Err(Error::dont_have_permission(format!("{} (token scope)", self.nick)))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Same as [`UserManager::has_permissions`] restricted to the token scopes
+    pub(crate) async fn has_permissions(
+        &self,
+        user_manager: &UserManager,
+        server: &str,
+        permission: &str,
+    ) -> CrateResult<()> {
+        self.scope_allows(server, |scope| scope == permission)?;
+        user_manager.has_permissions(self.nick.clone(), server, permission.to_string()).await
+    }
+
+    /// Same as [`UserManager::can_execute`] restricted to the token scopes
+    pub(crate) async fn can_execute(
+        &self,
+        user_manager: &UserManager,
+        server: &str,
+        command_line: &str,
+    ) -> CrateResult<()> {
+        self.scope_allows(server, |scope| permission::matches(scope, command_line, &self.nick))?;
+        user_manager.can_execute(self.nick.clone(), server, command_line).await
+    }
+
+    pub(crate) async fn require_admin(&self, user_manager: &UserManager) -> CrateResult<()> {
+        self.has_permissions(user_manager, ALL_SERVERS, "admin").await
+    }
+}
+
+fn bearer_token(req: &HttpRequest) -> Option<String> {
+    let value = req.headers().get(AUTHORIZATION)?.to_str().ok()?;
+    value.strip_prefix("Bearer ").map(|token| token.trim().to_string())
+}
+
+impl FromRequest for Caller {
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = CrateResult<Self>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        let identity = Identity::from_request(&req, payload);
+
+        Box::pin(async move {
+            let user_manager = req
+                .app_data::<web::Data<UserManager>>()
+                .ok_or_else(|| Error::server_error("missing user manager"))?;
+
+            if let Some(token) = bearer_token(&req) {
+                let tokens = req
+                    .app_data::<web::Data<TokenManager>>()
+                    .ok_or_else(|| Error::server_error("missing token manager"))?;
+                let verified = tokens.verify(&token).await?;
+                user_manager.ensure_active(&verified.nick).await?;
+
+                return Ok(Caller { nick: verified.nick, scopes: Some(verified.scopes) });
+            }
+
+            let identity = identity.await.map_err(|_| Error::not_logged_in("missing session"))?;
+            let nick = identity.id().map_err(Error::not_logged_in)?;
+            if let Err(err) = user_manager.ensure_active(&nick).await {
+                identity.logout();
+                return Err(err);
+            }
+
+            Ok(Caller { nick, scopes: None })
+        })
+    }
+}
@@ -0,0 +1,13 @@
+//! Timestamps stored in the database.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{CrateResult, Error};
+
+/// Seconds since the UNIX epoch
+pub(crate) fn unix_now() -> CrateResult<i64> {
+    Ok(SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(Error::server_error)?
+        .as_secs() as i64)
+}
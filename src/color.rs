@@ -0,0 +1,88 @@
+//! Minecraft `§` formatting codes of the command output.
+
+/// ANSI escape of a Minecraft formatting code, colors also reset the formatting as in the game
+fn ansi_code(code: char) -> Option<&'static str> {
+    let ansi = match code.to_ascii_lowercase() {
+        '0' => "0;30",
+        '1' => "0;34",
+        '2' => "0;32",
+        '3' => "0;36",
+        '4' => "0;31",
+        '5' => "0;35",
+        '6' => "0;33",
+        '7' => "0;37",
+        '8' => "0;90",
+        '9' => "0;94",
+        'a' => "0;92",
+        'b' => "0;96",
+        'c' => "0;91",
+        'd' => "0;95",
+        'e' => "0;93",
+        'f' => "0;97",
+        'k' => "8",
+        'l' => "1",
+        'm' => "9",
+        'n' => "4",
+        'o' => "3",
+        'r' => "0",
+        _ => return None,
+    };
+    Some(ansi)
+}
+
+/// Replaces the `§` formatting codes with ANSI escapes, unknown codes are dropped
+pub fn render_colors(text: &str) -> String {
+    let mut rendered = String::with_capacity(text.len());
+    let mut colored = false;
+
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '§' {
+            rendered.push(c);
+            continue;
+        }
+        if let Some(ansi) = chars.next().and_then(ansi_code) {
+            rendered.push_str("\x1b[");
+            rendered.push_str(ansi);
+            rendered.push('m');
+            colored = true;
+        }
+    }
+
+    if colored {
+        rendered.push_str("\x1b[0m");
+    }
+    rendered
+}
+
+/// Removes the `§` formatting codes, for output that isn't a terminal
+pub fn strip_colors(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+
+#[cfg(test)]
+mod color_test {
+    use super::*;
+
+    #[test]
+    fn render_minecraft_colors() {
+        assert_eq!(render_colors("no colors"), "no colors");
+        assert_eq!(
+            render_colors("§aonline§r: §lsteve"),
+            "\x1b[0;92monline\x1b[0m: \x1b[1msteve\x1b[0m",
+        );
+        assert_eq!(render_colors("§zunknown"), "unknown");
+        assert_eq!(strip_colors("§aonline§r: §lsteve"), "online: steve");
+    }
+}
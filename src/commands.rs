@@ -0,0 +1,836 @@
+//! Typed builders of the vanilla commands and parsers of their output.
+//!
+//! Builders validate their arguments, so a nick or a message can't smuggle extra
+//! arguments into the rendered line. Parsers turn the messages of the game into values,
+//! and its error messages into [`Error::CommandFailed`].
+//!
+//! ```ignore
+//! use mc_phone::commands::{Kick, List, Player};
+//!
+//! let players = conn.exec(&List).await?;
+//! conn.exec(&Kick::new(Player::new("Steve")?.into()).reason("afk")?).await?;
+//! ```
+
+use std::fmt;
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::{
+    color::strip_colors,
+    error::{CrateResult, Error},
+    pool::RconPool,
+    rcon::RconConnection,
+};
+
+/// Command with a typed result
+pub trait Command {
+    type Output;
+
+    /// Line sent over RCON
+    fn render(&self) -> String;
+
+    /// Reads the output of the server for this command
+    fn parse(&self, output: &str) -> CrateResult<Self::Output>;
+}
+
+impl RconConnection {
+    /// Runs a typed command and parses its output
+    pub async fn exec<C: Command>(&self, command: &C) -> CrateResult<C::Output> {
+        let response = self.exec_command(command.render()).await?;
+        command.parse(&response.output)
+    }
+}
+
+impl RconPool {
+    /// Runs a typed command on the least busy connection and parses its output
+    pub async fn exec<C: Command>(&self, command: &C) -> CrateResult<C::Output> {
+        let response = self.exec_command(command.render()).await?;
+        command.parse(&response.output)
+    }
+}
+
+/// Beginning of the messages sent by the game when a command fails
+const FAILURE_PREFIXES: [&str; 11] = [
+    "Unknown or incomplete command",
+    "Incorrect argument for command",
+    "No player was found",
+    "No entity was found",
+    "No targets matched selector",
+    "Only one player is allowed",
+    "Unknown item",
+    "That player does not exist",
+    "Invalid",
+    "Expected",
+    "Can't",
+];
+
+/// Output without formatting codes, failing when the game rejected the command
+fn output_text(output: &str) -> CrateResult<String> {
+    let text = strip_colors(output).trim().to_string();
+    if FAILURE_PREFIXES.iter().any(|prefix| text.starts_with(prefix)) {
+        return Err(Error::command_failed(text));
+    }
+    Ok(text)
+}
+
+fn unexpected(text: &str) -> Error {
+    Error::command_failed(format!("unexpected output: {text}"))
+}
+
+/// Free text must stay on one line
+fn check_text(field: &str, text: &str) -> CrateResult<()> {
+    if text.trim().is_empty() {
+        return Err(Error::invalid_command(format!("{field} can't be empty")));
+    }
+    if text.chars().any(char::is_control) {
+        return Err(Error::invalid_command(format!("{field} can't have control characters")));
+    }
+    Ok(())
+}
+
+/// Nick of a Java edition account
+#[derive(Debug, Clone, PartialEq)]
+pub struct Player(String);
+
+impl Player {
+    pub fn new(nick: impl Into<String>) -> CrateResult<Self> {
+        let nick = nick.into();
+        let valid_chars = nick.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if nick.is_empty() || nick.len() > 16 || !valid_chars {
+            return Err(Error::invalid_command(format!("invalid player nick: {nick:?}")));
+        }
+        Ok(Self(nick))
+    }
+}
+
+impl fmt::Display for Player {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Player or target selector, selectors with arguments aren't supported
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    Player(Player),
+    /// `@a`
+    AllPlayers,
+    /// `@p`
+    NearestPlayer,
+    /// `@r`
+    RandomPlayer,
+    /// `@e`
+    AllEntities,
+}
+
+impl Target {
+    pub fn parse(target: &str) -> CrateResult<Self> {
+        match target {
+            "@a" => Ok(Self::AllPlayers),
+            "@p" => Ok(Self::NearestPlayer),
+            "@r" => Ok(Self::RandomPlayer),
+            "@e" => Ok(Self::AllEntities),
+            nick => Player::new(nick).map(Self::Player),
+        }
+    }
+}
+
+impl From<Player> for Target {
+    fn from(player: Player) -> Self {
+        Self::Player(player)
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Player(player) => player.fmt(f),
+            Self::AllPlayers => f.write_str("@a"),
+            Self::NearestPlayer => f.write_str("@p"),
+            Self::RandomPlayer => f.write_str("@r"),
+            Self::AllEntities => f.write_str("@e"),
+        }
+    }
+}
+
+/// Any command, checked only for a valid name and arguments on a single line
+pub struct Raw {
+    line: String,
+}
+
+impl Raw {
+    pub fn new(command: &str, args: &[String]) -> CrateResult<Self> {
+        let name = command.trim_start_matches('/');
+        let valid_name = name.chars().all(|c| c.is_ascii_alphanumeric() || "_-:.".contains(c));
+        if name.is_empty() || !valid_name {
+            return Err(Error::invalid_command(format!("invalid command name: {command:?}")));
+        }
+        if args.iter().any(|arg| arg.chars().any(char::is_control)) {
+            return Err(Error::invalid_command("arguments can't have control characters"));
+        }
+
+        let mut line = format!("/{name}");
+        for arg in args {
+            line.push(' ');
+            line.push_str(arg);
+        }
+        Ok(Self { line })
+    }
+}
+
+impl Command for Raw {
+    type Output = String;
+
+    fn render(&self) -> String {
+        self.line.clone()
+    }
+
+    fn parse(&self, output: &str) -> CrateResult<String> {
+        Ok(output.to_string())
+    }
+}
+
+/// Broadcasts a message to every player
+pub struct Say {
+    message: String,
+}
+
+impl Say {
+    pub fn new(message: impl Into<String>) -> CrateResult<Self> {
+        let message = message.into();
+        check_text("message", &message)?;
+        Ok(Self { message })
+    }
+}
+
+impl Command for Say {
+    type Output = ();
+
+    fn render(&self) -> String {
+        format!("/say {}", self.message)
+    }
+
+    fn parse(&self, output: &str) -> CrateResult<()> {
+        output_text(output).map(drop)
+    }
+}
+
+/// Sends a JSON text component to players
+pub struct Tellraw {
+    target: Target,
+    component: Value,
+}
+
+impl Tellraw {
+    pub fn new(target: Target, component: Value) -> CrateResult<Self> {
+        if !(component.is_object() || component.is_array() || component.is_string()) {
+            return Err(Error::invalid_command("text component must be an object, array or string"));
+        }
+        Ok(Self { target, component })
+    }
+
+    /// Plain text component
+    pub fn text(target: Target, text: &str) -> CrateResult<Self> {
+        Self::new(target, json!({ "text": text }))
+    }
+}
+
+impl Command for Tellraw {
+    type Output = ();
+
+    fn render(&self) -> String {
+        // compact JSON keeps new lines escaped
+        format!("/tellraw {} {}", self.target, self.component)
+    }
+
+    fn parse(&self, output: &str) -> CrateResult<()> {
+        output_text(output).map(drop)
+    }
+}
+
+pub enum Destination {
+    Target(Target),
+    Position { x: f64, y: f64, z: f64 },
+}
+
+/// Moves entities to another entity or to absolute coordinates
+pub struct Teleport {
+    target: Target,
+    destination: Destination,
+}
+
+impl Teleport {
+    pub fn to_target(target: Target, destination: Target) -> Self {
+        Self { target, destination: Destination::Target(destination) }
+    }
+
+    pub fn to_position(target: Target, x: f64, y: f64, z: f64) -> CrateResult<Self> {
+        if ![x, y, z].iter().all(|c| c.is_finite()) {
+            return Err(Error::invalid_command("coordinates must be finite numbers"));
+        }
+        Ok(Self { target, destination: Destination::Position { x, y, z } })
+    }
+}
+
+impl Command for Teleport {
+    /// Number of teleported entities
+    type Output = u32;
+
+    fn render(&self) -> String {
+        match &self.destination {
+            Destination::Target(destination) => format!("/tp {} {destination}", self.target),
+            Destination::Position { x, y, z } => format!("/tp {} {x} {y} {z}", self.target),
+        }
+    }
+
+    fn parse(&self, output: &str) -> CrateResult<u32> {
+        let text = output_text(output)?;
+        let rest = text.strip_prefix("Teleported ").ok_or_else(|| unexpected(&text))?;
+        // "Teleported 3 entities to ..." or "Teleported Steve to ..."
+        let count = rest
+            .split_once(" entities ")
+            .and_then(|(count, _)| count.parse().ok())
+            .unwrap_or(1);
+        Ok(count)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameMode {
+    Survival,
+    Creative,
+    Adventure,
+    Spectator,
+}
+
+impl GameMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Survival => "survival",
+            Self::Creative => "creative",
+            Self::Adventure => "adventure",
+            Self::Spectator => "spectator",
+        }
+    }
+}
+
+pub struct Gamemode {
+    mode: GameMode,
+    target: Target,
+}
+
+impl Gamemode {
+    pub fn new(mode: GameMode, target: Target) -> Self {
+        Self { mode, target }
+    }
+}
+
+impl Command for Gamemode {
+    /// Players whose game mode changed, the ones already in `mode` are left out
+    type Output = Vec<String>;
+
+    fn render(&self) -> String {
+        format!("/gamemode {} {}", self.mode.as_str(), self.target)
+    }
+
+    fn parse(&self, output: &str) -> CrateResult<Vec<String>> {
+        let text = output_text(output)?;
+        // RCON joins the message of each player without a separator
+        text.split("Set ")
+            .filter(|message| !message.is_empty())
+            .map(|message| {
+                message
+                    .split_once("'s game mode to ")
+                    .map(|(nick, _)| nick.to_string())
+                    .ok_or_else(|| unexpected(&text))
+            })
+            .collect()
+    }
+}
+
+pub enum Whitelist {
+    Add(Player),
+    Remove(Player),
+    List,
+    On,
+    Off,
+    Reload,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "result", content = "players", rename_all = "snake_case")]
+pub enum WhitelistResult {
+    Changed,
+    /// The player or the whitelist was already in the requested state
+    Unchanged,
+    Players(Vec<String>),
+}
+
+impl Command for Whitelist {
+    type Output = WhitelistResult;
+
+    fn render(&self) -> String {
+        match self {
+            Self::Add(player) => format!("/whitelist add {player}"),
+            Self::Remove(player) => format!("/whitelist remove {player}"),
+            Self::List => "/whitelist list".to_string(),
+            Self::On => "/whitelist on".to_string(),
+            Self::Off => "/whitelist off".to_string(),
+            Self::Reload => "/whitelist reload".to_string(),
+        }
+    }
+
+    fn parse(&self, output: &str) -> CrateResult<WhitelistResult> {
+        let text = output_text(output)?;
+        if let Self::List = self {
+            return match text.split_once(": ") {
+                Some((_, players)) => Ok(WhitelistResult::Players(split_names(players))),
+                None if text.starts_with("There are no whitelisted players") => {
+                    Ok(WhitelistResult::Players(Vec::new()))
+                },
+                None => Err(unexpected(&text)),
+            };
+        }
+
+        let changed = ["Added ", "Removed ", "Whitelist is now turned", "Reloaded the whitelist"];
+        let unchanged = [
+            "Player is already whitelisted",
+            "Player is not whitelisted",
+            "Whitelist is already turned",
+        ];
+        if changed.iter().any(|prefix| text.starts_with(prefix)) {
+            Ok(WhitelistResult::Changed)
+        } else if unchanged.iter().any(|prefix| text.starts_with(prefix)) {
+            Ok(WhitelistResult::Unchanged)
+        } else {
+            Err(unexpected(&text))
+        }
+    }
+}
+
+fn split_names(names: &str) -> Vec<String> {
+    names
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// `true` for "changed" messages and `false` for the "Nothing changed" ones
+fn parse_toggle(output: &str, changed_prefix: &str) -> CrateResult<bool> {
+    let text = output_text(output)?;
+    if text.starts_with(changed_prefix) {
+        Ok(true)
+    } else if text.starts_with("Nothing changed") {
+        Ok(false)
+    } else {
+        Err(unexpected(&text))
+    }
+}
+
+pub struct Ban {
+    player: Player,
+    reason: Option<String>,
+}
+
+impl Ban {
+    pub fn new(player: Player) -> Self {
+        Self { player, reason: None }
+    }
+
+    pub fn reason(mut self, reason: impl Into<String>) -> CrateResult<Self> {
+        let reason = reason.into();
+        check_text("reason", &reason)?;
+        self.reason = Some(reason);
+        Ok(self)
+    }
+}
+
+impl Command for Ban {
+    /// `false` when the player was already banned
+    type Output = bool;
+
+    fn render(&self) -> String {
+        match &self.reason {
+            Some(reason) => format!("/ban {} {reason}", self.player),
+            None => format!("/ban {}", self.player),
+        }
+    }
+
+    fn parse(&self, output: &str) -> CrateResult<bool> {
+        parse_toggle(output, "Banned ")
+    }
+}
+
+pub struct Pardon(pub Player);
+
+impl Command for Pardon {
+    /// `false` when the player wasn't banned
+    type Output = bool;
+
+    fn render(&self) -> String {
+        format!("/pardon {}", self.0)
+    }
+
+    fn parse(&self, output: &str) -> CrateResult<bool> {
+        parse_toggle(output, "Unbanned ")
+    }
+}
+
+pub struct Kick {
+    target: Target,
+    reason: Option<String>,
+}
+
+impl Kick {
+    pub fn new(target: Target) -> Self {
+        Self { target, reason: None }
+    }
+
+    pub fn reason(mut self, reason: impl Into<String>) -> CrateResult<Self> {
+        let reason = reason.into();
+        check_text("reason", &reason)?;
+        self.reason = Some(reason);
+        Ok(self)
+    }
+}
+
+impl Command for Kick {
+    type Output = ();
+
+    fn render(&self) -> String {
+        match &self.reason {
+            Some(reason) => format!("/kick {} {reason}", self.target),
+            None => format!("/kick {}", self.target),
+        }
+    }
+
+    fn parse(&self, output: &str) -> CrateResult<()> {
+        let text = output_text(output)?;
+        if !text.starts_with("Kicked ") {
+            return Err(unexpected(&text));
+        }
+        Ok(())
+    }
+}
+
+pub struct Op(pub Player);
+
+impl Command for Op {
+    /// `false` when the player was already an operator
+    type Output = bool;
+
+    fn render(&self) -> String {
+        format!("/op {}", self.0)
+    }
+
+    fn parse(&self, output: &str) -> CrateResult<bool> {
+        parse_toggle(output, "Made ")
+    }
+}
+
+pub struct Deop(pub Player);
+
+impl Command for Deop {
+    /// `false` when the player wasn't an operator
+    type Output = bool;
+
+    fn render(&self) -> String {
+        format!("/deop {}", self.0)
+    }
+
+    fn parse(&self, output: &str) -> CrateResult<bool> {
+        parse_toggle(output, "Made ")
+    }
+}
+
+/// Largest amount accepted by `/give`, 100 stacks of 64
+const MAX_GIVE_COUNT: u32 = 6400;
+
+pub struct Give {
+    target: Target,
+    item: String,
+    count: u32,
+}
+
+impl Give {
+    /// `item` is an id like `diamond` or `minecraft:diamond`
+    pub fn new(target: Target, item: &str, count: u32) -> CrateResult<Self> {
+        let valid_char = |c: char| {
+            c.is_ascii_lowercase() || c.is_ascii_digit() || "_-./".contains(c)
+        };
+        let valid_part = |part: &str| !part.is_empty() && part.chars().all(valid_char);
+        let valid_item = match item.split_once(':') {
+            Some((namespace, path)) => valid_part(namespace) && valid_part(path),
+            None => valid_part(item),
+        };
+        if !valid_item {
+            return Err(Error::invalid_command(format!("invalid item id: {item:?}")));
+        }
+        if !(1..=MAX_GIVE_COUNT).contains(&count) {
+            return Err(Error::invalid_command(format!("count must be 1 to {MAX_GIVE_COUNT}")));
+        }
+
+        Ok(Self { target, item: item.to_string(), count })
+    }
+}
+
+impl Command for Give {
+    /// Amount of items given to each player
+    type Output = u32;
+
+    fn render(&self) -> String {
+        format!("/give {} {} {}", self.target, self.item, self.count)
+    }
+
+    fn parse(&self, output: &str) -> CrateResult<u32> {
+        let text = output_text(output)?;
+        text.strip_prefix("Gave ")
+            .and_then(|rest| rest.split_whitespace().next())
+            .and_then(|count| count.parse().ok())
+            .ok_or_else(|| unexpected(&text))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeOfDay {
+    Day,
+    Noon,
+    Night,
+    Midnight,
+    Ticks(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeQuery {
+    /// Ticks since the last dawn
+    Daytime,
+    /// Ticks since the world was created
+    Gametime,
+    /// Days since the world was created
+    Day,
+}
+
+pub enum Time {
+    Set(TimeOfDay),
+    Add(u32),
+    Query(TimeQuery),
+}
+
+impl Command for Time {
+    /// Time after the change, or the queried value
+    type Output = u64;
+
+    fn render(&self) -> String {
+        match self {
+            Self::Set(TimeOfDay::Day) => "/time set day".to_string(),
+            Self::Set(TimeOfDay::Noon) => "/time set noon".to_string(),
+            Self::Set(TimeOfDay::Night) => "/time set night".to_string(),
+            Self::Set(TimeOfDay::Midnight) => "/time set midnight".to_string(),
+            Self::Set(TimeOfDay::Ticks(ticks)) => format!("/time set {ticks}"),
+            Self::Add(ticks) => format!("/time add {ticks}"),
+            Self::Query(TimeQuery::Daytime) => "/time query daytime".to_string(),
+            Self::Query(TimeQuery::Gametime) => "/time query gametime".to_string(),
+            Self::Query(TimeQuery::Day) => "/time query day".to_string(),
+        }
+    }
+
+    fn parse(&self, output: &str) -> CrateResult<u64> {
+        // "Set the time to 1000" or "The time is 1000"
+        let text = output_text(output)?;
+        text.split_whitespace()
+            .last()
+            .and_then(|time| time.parse().ok())
+            .ok_or_else(|| unexpected(&text))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WeatherKind {
+    Clear,
+    Rain,
+    Thunder,
+}
+
+pub struct Weather {
+    kind: WeatherKind,
+    /// Seconds, random when missing
+    duration: Option<u32>,
+}
+
+impl Weather {
+    pub fn new(kind: WeatherKind) -> Self {
+        Self { kind, duration: None }
+    }
+
+    pub fn duration(mut self, seconds: u32) -> Self {
+        self.duration = Some(seconds);
+        self
+    }
+}
+
+impl Command for Weather {
+    type Output = ();
+
+    fn render(&self) -> String {
+        let kind = match self.kind {
+            WeatherKind::Clear => "clear",
+            WeatherKind::Rain => "rain",
+            WeatherKind::Thunder => "thunder",
+        };
+        match self.duration {
+            Some(seconds) => format!("/weather {kind} {seconds}"),
+            None => format!("/weather {kind}"),
+        }
+    }
+
+    fn parse(&self, output: &str) -> CrateResult<()> {
+        let text = output_text(output)?;
+        // older versions answer "Changing to clear weather"
+        if !(text.starts_with("Set the weather") || text.starts_with("Changing to")) {
+            return Err(unexpected(&text));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct SaveAll {
+    /// Writes every chunk to the disk before answering
+    pub flush: bool,
+}
+
+impl Command for SaveAll {
+    type Output = ();
+
+    fn render(&self) -> String {
+        if self.flush { "/save-all flush".to_string() } else { "/save-all".to_string() }
+    }
+
+    fn parse(&self, output: &str) -> CrateResult<()> {
+        let text = output_text(output)?;
+        if text.contains("failed") || !text.contains("Sav") {
+            return Err(Error::command_failed(text));
+        }
+        Ok(())
+    }
+}
+
+/// Players online
+pub struct List;
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct PlayerList {
+    pub online: u32,
+    pub max: u32,
+    pub players: Vec<String>,
+}
+
+impl Command for List {
+    type Output = PlayerList;
+
+    fn render(&self) -> String {
+        "/list".to_string()
+    }
+
+    fn parse(&self, output: &str) -> CrateResult<PlayerList> {
+        // "There are 2 of a max of 20 players online: Steve, Alex"
+        let text = output_text(output)?;
+        let parsed = || {
+            let rest = text.strip_prefix("There are ")?;
+            let (online, rest) = rest.split_once(" of a max of ")?;
+            let (max, players) = rest.split_once(" players online:")?;
+            Some(PlayerList {
+                online: online.trim().parse().ok()?,
+                max: max.trim().parse().ok()?,
+                players: split_names(players),
+            })
+        };
+        parsed().ok_or_else(|| unexpected(&text))
+    }
+}
+
+
+#[cfg(test)]
+mod commands_test {
+    use super::*;
+
+    fn steve() -> Target {
+        Player::new("Steve").unwrap().into()
+    }
+
+    #[test]
+    fn reject_injected_arguments() {
+        assert!(Player::new("Steve @a").is_err());
+        assert!(Player::new("").is_err());
+        assert!(Target::parse("@a[distance=..5]").is_err());
+        assert!(Say::new("hi\n/op Steve").is_err());
+        assert!(Ban::new(Player::new("Steve").unwrap()).reason("").is_err());
+        assert!(Give::new(steve(), "diamond 64 @a", 1).is_err());
+        assert!(Give::new(steve(), "diamond", 0).is_err());
+        assert!(Teleport::to_position(steve(), f64::NAN, 0.0, 0.0).is_err());
+        assert!(Raw::new("op Steve", &[]).is_err());
+        assert!(Raw::new("say", &["hi\r\nop Steve".to_string()]).is_err());
+    }
+
+    #[test]
+    fn render_commands() {
+        assert_eq!(Say::new("hello world").unwrap().render(), "/say hello world");
+        assert_eq!(
+            Tellraw::text(Target::AllPlayers, "say \"hi\"\n").unwrap().render(),
+            r#"/tellraw @a {"text":"say \"hi\"\n"}"#,
+        );
+        assert_eq!(
+            Teleport::to_position(steve(), 1.5, -60.0, 3.0).unwrap().render(),
+            "/tp Steve 1.5 -60 3",
+        );
+        assert_eq!(Gamemode::new(GameMode::Creative, steve()).render(), "/gamemode creative Steve");
+        assert_eq!(
+            Give::new(steve(), "minecraft:diamond", 64).unwrap().render(),
+            "/give Steve minecraft:diamond 64",
+        );
+        assert_eq!(Time::Set(TimeOfDay::Ticks(1000)).render(), "/time set 1000");
+        assert_eq!(Weather::new(WeatherKind::Rain).duration(600).render(), "/weather rain 600");
+        assert_eq!(SaveAll { flush: true }.render(), "/save-all flush");
+        assert_eq!(Raw::new("/list", &[]).unwrap().render(), "/list");
+    }
+
+    #[test]
+    fn parse_outputs() {
+        assert_eq!(
+            List.parse("There are 2 of a max of 20 players online: Steve, Alex").unwrap(),
+            PlayerList { online: 2, max: 20, players: vec!["Steve".into(), "Alex".into()] },
+        );
+        let empty = List.parse("There are 0 of a max of 20 players online: ").unwrap();
+        assert!(empty.players.is_empty());
+
+        assert_eq!(
+            Whitelist::List.parse("There are 1 whitelisted player(s): Steve").unwrap(),
+            WhitelistResult::Players(vec!["Steve".into()]),
+        );
+        let add = Whitelist::Add(Player::new("Steve").unwrap());
+        assert_eq!(add.parse("Player is already whitelisted").unwrap(), WhitelistResult::Unchanged);
+
+        let gamemode = Gamemode::new(GameMode::Creative, Target::AllPlayers);
+        let output = "Set Steve's game mode to Creative ModeSet Alex's game mode to Creative Mode";
+        assert_eq!(gamemode.parse(output).unwrap(), vec!["Steve".to_string(), "Alex".to_string()]);
+
+        let op = Op(Player::new("Steve").unwrap());
+        assert!(op.parse("Made Steve a server operator").unwrap());
+        assert!(!op.parse("Nothing changed. The player already is an operator").unwrap());
+
+        assert_eq!(Time::Query(TimeQuery::Daytime).parse("The time is 6000").unwrap(), 6000);
+        assert_eq!(Teleport::to_target(Target::AllEntities, steve())
+            .parse("Teleported 3 entities to Steve").unwrap(), 3);
+        assert_eq!(Give::new(steve(), "diamond", 64).unwrap()
+            .parse("Gave 64 [Diamond] to Steve").unwrap(), 64);
+
+        assert!(matches!(
+            Kick::new(steve()).parse("No player was found"),
+            Err(Error::CommandFailed { .. }),
+        ));
+        assert!(matches!(List.parse("§cUnknown command"), Err(Error::CommandFailed { .. })));
+    }
+}
@@ -0,0 +1,459 @@
+//! Settings from a TOML file, overridden by environment variables and then by CLI flags.
+//!
+//! ```toml
+//! [database]
+//! url = "sqlite://mc-phone.db?mode=rwc"
+//!
+//! [http]
+//! bind = "127.0.0.1:6969"
+//! workers = 2
+//! session_ttl = 86400
+//! # pages of other sites allowed to open the WebSocket console, besides this server
+//! allowed_origins = ["https://admin.example.com"]
+//!
+//! [tls]
+//! cert = "cert.pem"
+//! key = "key.pem"
+//! # or a certificate for localhost generated at startup, for development
+//! # self_signed = true
+//! # plain HTTP listener answering with redirects to the HTTPS one
+//! redirect_bind = "0.0.0.0:80"
+//!
+//! [log]
+//! level = "info,sqlx=warn"
+//!
+//! [rcon]
+//! # RCON connections opened to each server, the extra ones only under load
+//! pool_size = 4
+//! idle_timeout = 60
+//! health_interval = 30
+//! # seconds before a silent server fails with a timeout
+//! connect_timeout = 5
+//! auth_timeout = 5
+//! command_timeout = 10
+//!
+//! [[servers]]
+//! name = "default"
+//! host = "127.0.0.1"
+//! port = 25575
+//! password = "rcon-password"
+//! # server-port and query.port of server.properties, for GET /servers/{name}/status
+//! game_port = 25565
+//! query_port = 25565
+//! ```
+
+use std::{
+    collections::HashSet,
+    fs,
+    net::ToSocketAddrs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::{arg, Arg, ArgMatches};
+use serde::Deserialize;
+
+use crate::{
+    error::{CrateResult, Error},
+    pool::PoolConfig,
+    rcon::Timeouts,
+    registry::{RconTarget, DEFAULT_SERVER},
+};
+
+/// Read from the working directory when `--config` is missing
+pub(crate) const DEFAULT_CONFIG_FILE: &str = "mc-phone.toml";
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: String,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self { url: "sqlite://mc-phone.db?mode=rwc".to_string() }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    pub bind: String,
+    pub workers: usize,
+    /// Lifetime of the login sessions in seconds
+    pub session_ttl: u64,
+    /// `Origin`s besides the own host allowed to open the WebSocket console
+    pub allowed_origins: Vec<String>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            bind: "127.0.0.1:6969".to_string(),
+            workers: 2,
+            session_ttl: 24 * 60 * 60,
+            allowed_origins: Vec::new(),
+        }
+    }
+}
+
+/// HTTPS settings, the web server only speaks plain HTTP without them
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    /// PEM file of the certificate chain
+    pub cert: Option<PathBuf>,
+    /// PEM file of the private key
+    pub key: Option<PathBuf>,
+    /// Generates a certificate for localhost instead of reading `cert` and `key`
+    pub self_signed: bool,
+    /// Address of a plain HTTP listener redirecting to HTTPS
+    pub redirect_bind: Option<String>,
+}
+
+/// RCON connections to every server, see [`PoolConfig`] and [`Timeouts`]
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RconConfig {
+    pub pool_size: usize,
+    /// Seconds before an unused extra connection is closed
+    pub idle_timeout: u64,
+    /// Seconds between two pings of the idle connections
+    pub health_interval: u64,
+    /// Seconds to open the TCP socket
+    pub connect_timeout: u64,
+    /// Seconds to get the reply to the password
+    pub auth_timeout: u64,
+    /// Seconds to get the whole response of a command
+    pub command_timeout: u64,
+}
+
+impl Default for RconConfig {
+    fn default() -> Self {
+        let pool = PoolConfig::default();
+        Self {
+            pool_size: pool.size,
+            idle_timeout: pool.idle_timeout.as_secs(),
+            health_interval: pool.health_interval.as_secs(),
+            connect_timeout: pool.timeouts.connect.as_secs(),
+            auth_timeout: pool.timeouts.auth.as_secs(),
+            command_timeout: pool.timeouts.command.as_secs(),
+        }
+    }
+}
+
+impl RconConfig {
+    pub(crate) fn timeouts(&self) -> Timeouts {
+        Timeouts {
+            connect: Duration::from_secs(self.connect_timeout),
+            auth: Duration::from_secs(self.auth_timeout),
+            command: Duration::from_secs(self.command_timeout),
+        }
+    }
+
+    pub fn pool(&self) -> PoolConfig {
+        PoolConfig {
+            size: self.pool_size,
+            idle_timeout: Duration::from_secs(self.idle_timeout),
+            health_interval: Duration::from_secs(self.health_interval),
+            timeouts: self.timeouts(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// Same syntax of `RUST_LOG`
+    pub level: String,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self { level: "error".to_string() }
+    }
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub database: DatabaseConfig,
+    pub http: HttpConfig,
+    pub tls: Option<TlsConfig>,
+    pub log: LogConfig,
+    pub rcon: RconConfig,
+    pub servers: Vec<RconTarget>,
+}
+
+/// Flags accepted by every subcommand
+pub fn global_args() -> [Arg; 3] {
+    [
+        arg!(--config <FILE> "TOML config file, mc-phone.toml when it exists")
+            .env("MC_PHONE_CONFIG")
+            .value_parser(clap::value_parser!(PathBuf))
+            .global(true),
+        arg!(--database_url <URL> "SQLite database")
+            .env("DATABASE_URL")
+            .global(true),
+        arg!(--log_level <FILTER> "log filter, like info or mc_phone=debug")
+            .env("RUST_LOG")
+            .global(true),
+    ]
+}
+
+/// Flags of the `server` subcommand overriding the `[http]` section
+pub fn http_args() -> [Arg; 3] {
+    [
+        arg!(--bind <ADDR> "address of the web server").env("MC_PHONE_BIND"),
+        arg!(--workers <N> "number of HTTP workers")
+            .env("MC_PHONE_WORKERS")
+            .value_parser(clap::value_parser!(usize)),
+        arg!(--session_ttl <SECONDS> "lifetime of the login sessions")
+            .env("MC_PHONE_SESSION_TTL")
+            .value_parser(clap::value_parser!(u64)),
+    ]
+}
+
+fn override_with<T: Clone + Send + Sync + 'static>(value: &mut T, matches: &ArgMatches, id: &str) {
+    if let Ok(Some(arg)) = matches.try_get_one::<T>(id) {
+        *value = arg.clone();
+    }
+}
+
+impl TlsConfig {
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        match (&self.cert, &self.key, self.self_signed) {
+            (None, None, true) => {},
+            (_, _, true) => {
+                problems.push("tls.self_signed can't be used with tls.cert or tls.key".into());
+            },
+            (cert, key, false) => {
+                for (field, path) in [("tls.cert", cert), ("tls.key", key)] {
+                    match path {
+                        Some(path) if !path.is_file() => {
+                            problems.push(format!("{field} not found: {}", path.display()));
+                        },
+                        Some(_) => {},
+                        None => problems.push(format!("{field} is missing")),
+                    }
+                }
+            },
+        }
+        if let Some(bind) = &self.redirect_bind
+            && bind.to_socket_addrs().is_err()
+        {
+            problems.push(format!("tls.redirect_bind is not a valid address: {bind}"));
+        }
+
+        problems
+    }
+}
+
+impl Config {
+    /// Reads `path`, or [`DEFAULT_CONFIG_FILE`] when it exists, or the defaults
+    pub fn load(path: Option<&Path>) -> CrateResult<Self> {
+        let default = Path::new(DEFAULT_CONFIG_FILE);
+        let path = match path {
+            Some(path) => path,
+            None if default.exists() => default,
+            None => return Ok(Self::default()),
+        };
+
+        let content = fs::read_to_string(path)
+            .map_err(|err| Error::invalid_config(format!("{}: {err}", path.display())))?;
+        toml::from_str(&content)
+            .map_err(|err| Error::invalid_config(format!("{}: {err}", path.display())))
+    }
+
+    /// Values from the environment or the command line replace the ones of the file,
+    /// clap already gives the flags precedence over the environment
+    pub fn apply_args(&mut self, matches: &ArgMatches) {
+        override_with(&mut self.database.url, matches, "database_url");
+        override_with(&mut self.log.level, matches, "log_level");
+        override_with(&mut self.http.bind, matches, "bind");
+        override_with(&mut self.http.workers, matches, "workers");
+        override_with(&mut self.http.session_ttl, matches, "session_ttl");
+    }
+
+    /// `--host`, `--port` and `--password` of the `server` subcommand replace the
+    /// [`DEFAULT_SERVER`] of the file
+    pub fn apply_default_server(&mut self, matches: &ArgMatches) -> CrateResult<()> {
+        let rcon_flags = ["host", "port", "password"];
+        if rcon_flags.iter().any(|id| matches!(matches.try_contains_id(id), Ok(true))) {
+            let target = self.rcon_target(DEFAULT_SERVER, matches)?;
+            self.servers.retain(|server| server.name != DEFAULT_SERVER);
+            self.servers.push(target);
+        }
+        Ok(())
+    }
+
+    /// The server `name` of the file with `--host`, `--port` and `--password` on top
+    pub fn rcon_target(&self, name: &str, matches: &ArgMatches) -> CrateResult<RconTarget> {
+        let configured = self.servers.iter().find(|server| server.name == name);
+        let host = matches.try_get_one::<String>("host").ok().flatten().cloned();
+        let port = matches.try_get_one::<u16>("port").ok().flatten().copied();
+        let password = matches.try_get_one::<String>("password").ok().flatten().cloned();
+
+        let missing = |field: &str| {
+            Error::invalid_config(format!("missing RCON {field} of the server {name}"))
+        };
+        Ok(RconTarget {
+            name: name.to_string(),
+            host: host
+                .or_else(|| configured.map(|server| server.host.clone()))
+                .ok_or_else(|| missing("host"))?,
+            port: port
+                .or_else(|| configured.map(|server| server.port))
+                .ok_or_else(|| missing("port"))?,
+            password: password
+                .or_else(|| configured.map(|server| server.password.clone()))
+                .ok_or_else(|| missing("password"))?,
+            query_port: configured.and_then(|server| server.query_port),
+            game_port: configured.and_then(|server| server.game_port),
+        })
+    }
+
+    /// Every problem found, empty when the config is valid
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if !self.database.url.starts_with("sqlite:") {
+            problems.push(format!("database.url must be a sqlite: URL, got {}", self.database.url));
+        }
+        if self.http.bind.to_socket_addrs().is_err() {
+            problems.push(format!("http.bind is not a valid address: {}", self.http.bind));
+        }
+        if self.http.workers == 0 {
+            problems.push("http.workers must be at least 1".to_string());
+        }
+        if self.http.session_ttl == 0 {
+            problems.push("http.session_ttl must be at least 1 second".to_string());
+        }
+        for origin in &self.http.allowed_origins {
+            let valid = origin.split_once("://").is_some_and(|(scheme, host)| {
+                !scheme.is_empty() && !host.is_empty() && !host.contains('/')
+            });
+            if !valid {
+                problems.push(format!(
+                    "http.allowed_origins must be like https://host[:port], got {origin}"
+                ));
+            }
+        }
+        if self.rcon.pool_size == 0 {
+            problems.push("rcon.pool_size must be at least 1".to_string());
+        }
+        let rcon_seconds = [
+            ("health_interval", self.rcon.health_interval),
+            ("connect_timeout", self.rcon.connect_timeout),
+            ("auth_timeout", self.rcon.auth_timeout),
+            ("command_timeout", self.rcon.command_timeout),
+        ];
+        for (field, seconds) in rcon_seconds {
+            if seconds == 0 {
+                problems.push(format!("rcon.{field} must be at least 1 second"));
+            }
+        }
+        if let Some(tls) = &self.tls {
+            problems.extend(tls.validate());
+        }
+
+        let mut names = HashSet::new();
+        for server in &self.servers {
+            if server.name.is_empty() {
+                problems.push("servers.name can't be empty".to_string());
+            } else if !names.insert(server.name.as_str()) {
+                problems.push(format!("server {} is defined twice", server.name));
+            }
+            if server.host.is_empty() {
+                problems.push(format!("server {} has an empty host", server.name));
+            }
+            if [Some(server.port), server.query_port, server.game_port].contains(&Some(0)) {
+                problems.push(format!("server {} has port 0", server.name));
+            }
+        }
+
+        problems
+    }
+}
+
+
+#[cfg(test)]
+mod config_test {
+    use clap::Command;
+
+    use super::*;
+
+    const CONFIG: &str = r#"
+        [http]
+        bind = "0.0.0.0:8080"
+        workers = 4
+
+        [[servers]]
+        name = "default"
+        host = "mc.local"
+        port = 25575
+        password = "secret"
+    "#;
+
+    fn command() -> Command {
+        Command::new("mc-phone")
+            .args(global_args())
+            .args(http_args())
+            .arg(arg!(--host <HOST>))
+            .arg(arg!(--port <PORT>).value_parser(clap::value_parser!(u16)))
+            .arg(arg!(--password <PASSWORD>))
+    }
+
+    #[test]
+    fn flags_override_the_file() {
+        let mut config: Config = toml::from_str(CONFIG).unwrap();
+        let matches = command()
+            .try_get_matches_from(["mc-phone", "--workers", "8", "--port", "25576"])
+            .unwrap();
+        config.apply_args(&matches);
+        config.apply_default_server(&matches).unwrap();
+
+        assert_eq!(config.http.bind, "0.0.0.0:8080");
+        assert_eq!(config.http.workers, 8);
+        assert_eq!(config.http.session_ttl, 24 * 60 * 60);
+        assert_eq!(config.servers.len(), 1);
+        assert_eq!(config.servers[0].host, "mc.local");
+        assert_eq!(config.servers[0].port, 25576);
+        assert!(config.validate().is_empty());
+    }
+
+    #[test]
+    fn report_invalid_values() {
+        assert!(toml::from_str::<Config>("[http]\nport = 80").is_err());
+
+        let config: Config = toml::from_str(r#"
+            [http]
+            bind = "not an address"
+            workers = 0
+            allowed_origins = ["admin.example.com"]
+
+            [rcon]
+            pool_size = 0
+            command_timeout = 0
+
+            [tls]
+            cert = "missing-cert.pem"
+
+            [[servers]]
+            name = "survival"
+            host = "mc.local"
+            port = 0
+            password = ""
+
+            [[servers]]
+            name = "survival"
+            host = "mc.local"
+            port = 25575
+            password = ""
+        "#).unwrap();
+
+        assert_eq!(config.validate().len(), 9);
+    }
+}
@@ -0,0 +1,128 @@
+use std::path::PathBuf;
+
+use rustyline::{
+    completion::Completer, error::ReadlineError, history::DefaultHistory, Context, Editor, Helper,
+    Highlighter, Hinter, Validator,
+};
+
+use crate::{
+    color::render_colors,
+    error::{CrateResult, Error},
+    rcon::RconConnection,
+};
+
+const PROMPT: &str = "> ";
+const HISTORY_FILE: &str = ".mc-phone_history";
+
+/// Vanilla commands offered by the tab completion
+const COMMANDS: &[&str] = &[
+    "advancement", "attribute", "ban", "ban-ip", "banlist", "bossbar", "clear", "clone",
+    "damage", "data", "datapack", "debug", "defaultgamemode", "deop", "difficulty", "effect",
+    "enchant", "execute", "experience", "fill", "fillbiome", "forceload", "function",
+    "gamemode", "gamerule", "give", "help", "item", "jfr", "kick", "kill", "list", "locate",
+    "loot", "me", "msg", "op", "pardon", "pardon-ip", "particle", "perf", "place",
+    "playsound", "random", "recipe", "reload", "return", "ride", "save-all", "save-off",
+    "save-on", "say", "schedule", "scoreboard", "seed", "setblock", "setidletimeout",
+    "setworldspawn", "spawnpoint", "spectate", "spreadplayers", "stop", "stopsound",
+    "summon", "tag", "team", "teammsg", "teleport", "tell", "tellraw", "tick", "time",
+    "title", "tm", "tp", "transfer", "trigger", "w", "weather", "whitelist", "worldborder",
+    "xp",
+];
+
+/// Completes the command name, the first word of the line
+#[derive(Helper, Hinter, Highlighter, Validator)]
+struct ConsoleHelper;
+
+impl Completer for ConsoleHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let typed = &line[..pos];
+        if typed.contains(char::is_whitespace) {
+            return Ok((pos, Vec::new()));
+        }
+
+        let start = if typed.starts_with('/') { 1 } else { 0 };
+        let candidates = COMMANDS
+            .iter()
+            .filter(|command| command.starts_with(&typed[start..]))
+            .map(|command| command.to_string())
+            .collect();
+
+        Ok((start, candidates))
+    }
+}
+
+fn history_path() -> PathBuf {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(HISTORY_FILE)
+}
+
+/// Reads commands until Ctrl-D, the history is kept in `~/.mc-phone_history`
+pub async fn run(connection: RconConnection) -> CrateResult<()> {
+    let mut editor: Editor<ConsoleHelper, DefaultHistory> =
+        Editor::new().map_err(Error::server_error)?;
+    editor.set_helper(Some(ConsoleHelper));
+
+    let history = history_path();
+    // the file doesn't exist on the first run
+    let _ = editor.load_history(&history);
+
+    loop {
+        // readline blocks the thread until the user hits enter
+        let (returned, line) = tokio::task::spawn_blocking(move || {
+            let line = editor.readline(PROMPT);
+            (editor, line)
+        })
+        .await
+        .map_err(Error::server_error)?;
+        editor = returned;
+
+        let line = match line {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(Error::server_error(err)),
+        };
+        let command = line.trim();
+        if command.is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(command);
+
+        match connection.exec_command(command.to_string()).await {
+            Ok(response) => println!("{}", render_colors(&response.output)),
+            Err(err) => eprintln!("{err}"),
+        }
+    }
+
+    editor.save_history(&history).map_err(Error::server_error)
+}
+
+
+#[cfg(test)]
+mod console_test {
+    use rustyline::history::DefaultHistory;
+
+    use super::*;
+
+    #[test]
+    fn complete_command_names() {
+        let history = DefaultHistory::new();
+        let ctx = Context::new(&history);
+
+        let (start, candidates) = ConsoleHelper.complete("/save-o", 7, &ctx).unwrap();
+        assert_eq!(start, 1);
+        assert_eq!(candidates, vec!["save-off", "save-on"]);
+
+        let (_, candidates) = ConsoleHelper.complete("say sa", 6, &ctx).unwrap();
+        assert!(candidates.is_empty());
+    }
+}
@@ -1,10 +1,14 @@
+
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use serde::Serialize;
 use snafu::prelude::*;
 
-pub(crate) type CrateResult<T, E = Error> = std::result::Result<T, E>;
+pub type CrateResult<T, E = Error> = std::result::Result<T, E>;
 
 
 #[derive(Debug, Snafu)]
-pub(crate) enum Error {
+#[allow(clippy::enum_variant_names)]
+pub enum Error {
     #[snafu(display("Fail when read/write with tcp connection: {}", raw_err))]
     ConnectionError { raw_err: String },
     
@@ -18,24 +22,185 @@ pub(crate) enum Error {
     CantCreateUser { raw_err : String },
     
     #[snafu(display("user don't have enough permission: {}", raw_err))]
-    DontHavePermission { raw_err : String }
+    DontHavePermission { raw_err : String },
+    
+    #[snafu(display("user is not logged in: {}", raw_err))]
+    NotLoggedIn { raw_err : String },
+    
+    #[snafu(display("RCON authentication failed, check the RCON password: {}", raw_err))]
+    RconAuthFailed { raw_err : String },
+    
+    #[snafu(display("RCON connection is not available: {}", raw_err))]
+    RconUnavailable { raw_err : String },
+    
+    #[snafu(display("server not found: {}", raw_err))]
+    ServerNotFound { raw_err : String },
+    
+    #[snafu(display("user not found: {}", raw_err))]
+    UserNotFound { raw_err : String },
+    
+    #[snafu(display("user is disabled: {}", raw_err))]
+    UserDisabled { raw_err : String },
+    
+    #[snafu(display("permission not found: {}", raw_err))]
+    PermissionNotFound { raw_err : String },
+    
+    #[snafu(display("role not found: {}", raw_err))]
+    RoleNotFound { raw_err : String },
+    
+    #[snafu(display("token not found: {}", raw_err))]
+    TokenNotFound { raw_err : String },
+    
+    #[snafu(display("session not found: {}", raw_err))]
+    SessionNotFound { raw_err : String },
+    
+    #[snafu(display("invalid configuration: {}", raw_err))]
+    InvalidConfig { raw_err : String },
+    
+    #[snafu(display("invalid command: {}", raw_err))]
+    InvalidCommand { raw_err : String },
+    
+    #[snafu(display("command failed: {}", raw_err))]
+    CommandFailed { raw_err : String },
+    
+    #[snafu(display("timed out: {}", raw_err))]
+    Timeout { raw_err : String },
 }
 
 impl Error {    
-    pub(crate) fn connection_error<S: ToString>(s: S) -> Self {
+    pub fn connection_error<S: ToString>(s: S) -> Self {
         Self::ConnectionError { raw_err: s.to_string() }
     }
     
-    pub(crate) fn server_error<S: ToString>(s: S) -> Self {
+    pub fn server_error<S: ToString>(s: S) -> Self {
         Self::ServerError { raw_err: s.to_string() }
     }
     
-    pub(crate) fn cant_create_user<S: ToString>(s: S) -> Self {
+    pub fn cant_create_user<S: ToString>(s: S) -> Self {
         Self::CantCreateUser { raw_err: s.to_string() }
     }
     
-    pub(crate) fn dont_have_permission<S: ToString>(s: S) -> Self {
+    pub fn dont_have_permission<S: ToString>(s: S) -> Self {
         Self::DontHavePermission { raw_err: s.to_string() }
     }
+    
+    pub fn not_logged_in<S: ToString>(s: S) -> Self {
+        Self::NotLoggedIn { raw_err: s.to_string() }
+    }
+    
+    pub fn rcon_auth_failed<S: ToString>(s: S) -> Self {
+        Self::RconAuthFailed { raw_err: s.to_string() }
+    }
+    
+    pub fn rcon_unavailable<S: ToString>(s: S) -> Self {
+        Self::RconUnavailable { raw_err: s.to_string() }
+    }
+    
+    pub fn server_not_found<S: ToString>(s: S) -> Self {
+        Self::ServerNotFound { raw_err: s.to_string() }
+    }
+    
+    pub fn user_not_found<S: ToString>(s: S) -> Self {
+        Self::UserNotFound { raw_err: s.to_string() }
+    }
+    
+    pub fn user_disabled<S: ToString>(s: S) -> Self {
+        Self::UserDisabled { raw_err: s.to_string() }
+    }
+    
+    pub fn permission_not_found<S: ToString>(s: S) -> Self {
+        Self::PermissionNotFound { raw_err: s.to_string() }
+    }
+    
+    pub fn role_not_found<S: ToString>(s: S) -> Self {
+        Self::RoleNotFound { raw_err: s.to_string() }
+    }
+    
+    pub fn token_not_found<S: ToString>(s: S) -> Self {
+        Self::TokenNotFound { raw_err: s.to_string() }
+    }
+    
+    pub fn session_not_found<S: ToString>(s: S) -> Self {
+        Self::SessionNotFound { raw_err: s.to_string() }
+    }
+    
+    pub fn invalid_config<S: ToString>(s: S) -> Self {
+        Self::InvalidConfig { raw_err: s.to_string() }
+    }
+    
+    pub fn invalid_command<S: ToString>(s: S) -> Self {
+        Self::InvalidCommand { raw_err: s.to_string() }
+    }
+    
+    pub fn command_failed<S: ToString>(s: S) -> Self {
+        Self::CommandFailed { raw_err: s.to_string() }
+    }
+    
+    pub fn timeout<S: ToString>(s: S) -> Self {
+        Self::Timeout { raw_err: s.to_string() }
+    }
+    
+    /// Stable identifier of the error sent to HTTP clients
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::ConnectionError { .. } => "connection_error",
+            Self::ServerError { .. } => "server_error",
+            Self::PasswordDontMatch { .. } => "password_dont_match",
+            Self::CantCreateUser { .. } => "cant_create_user",
+            Self::DontHavePermission { .. } => "dont_have_permission",
+            Self::NotLoggedIn { .. } => "not_logged_in",
+            Self::RconAuthFailed { .. } => "rcon_auth_failed",
+            Self::RconUnavailable { .. } => "rcon_unavailable",
+            Self::ServerNotFound { .. } => "server_not_found",
+            Self::UserNotFound { .. } => "user_not_found",
+            Self::UserDisabled { .. } => "user_disabled",
+            Self::PermissionNotFound { .. } => "permission_not_found",
+            Self::RoleNotFound { .. } => "role_not_found",
+            Self::TokenNotFound { .. } => "token_not_found",
+            Self::SessionNotFound { .. } => "session_not_found",
+            Self::InvalidConfig { .. } => "invalid_config",
+            Self::InvalidCommand { .. } => "invalid_command",
+            Self::CommandFailed { .. } => "command_failed",
+            Self::Timeout { .. } => "timeout",
+        }
+    }
 }
 
+#[derive(Serialize)]
+struct ErrorBody {
+    error: &'static str,
+    message: String,
+}
+
+impl ResponseError for Error {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::ConnectionError { .. } => StatusCode::BAD_GATEWAY,
+            Self::ServerError { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::PasswordDontMatch { .. } => StatusCode::UNAUTHORIZED,
+            Self::CantCreateUser { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::DontHavePermission { .. } => StatusCode::FORBIDDEN,
+            Self::NotLoggedIn { .. } => StatusCode::UNAUTHORIZED,
+            Self::RconAuthFailed { .. } => StatusCode::BAD_GATEWAY,
+            Self::RconUnavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            Self::ServerNotFound { .. } => StatusCode::NOT_FOUND,
+            Self::UserNotFound { .. } => StatusCode::NOT_FOUND,
+            Self::UserDisabled { .. } => StatusCode::FORBIDDEN,
+            Self::PermissionNotFound { .. } => StatusCode::NOT_FOUND,
+            Self::RoleNotFound { .. } => StatusCode::NOT_FOUND,
+            Self::TokenNotFound { .. } => StatusCode::NOT_FOUND,
+            Self::SessionNotFound { .. } => StatusCode::NOT_FOUND,
+            Self::InvalidConfig { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::InvalidCommand { .. } => StatusCode::BAD_REQUEST,
+            Self::CommandFailed { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::Timeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+        }
+    }
+    
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(ErrorBody {
+            error: self.kind(),
+            message: self.to_string(),
+        })
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    fs,
+    io::{self, BufRead},
+    path::Path,
+};
+
+use serde::Serialize;
+
+use crate::{
+    color::strip_colors,
+    error::{CrateResult, Error},
+    rcon::{RconConnection, RconResponse},
+};
+
+/// Exit code when the server rejects the RCON password
+const EXIT_AUTH_FAILED: i32 = 2;
+/// Exit code when the server can't be reached or the connection drops
+const EXIT_CONNECTION_FAILED: i32 = 3;
+
+#[derive(Serialize)]
+struct ExecLine<'a> {
+    command: &'a str,
+    #[serde(flatten)]
+    response: &'a RconResponse,
+}
+
+/// Process exit code for an error of [`run`]
+pub fn exit_code(err: &Error) -> i32 {
+    match err {
+        Error::RconAuthFailed { .. } => EXIT_AUTH_FAILED,
+        Error::ConnectionError { .. } | Error::RconUnavailable { .. } | Error::Timeout { .. } => {
+            EXIT_CONNECTION_FAILED
+        },
+        _ => 1,
+    }
+}
+
+/// Blank lines and lines starting with `#` are skipped
+fn parse_lines(lines: impl Iterator<Item = io::Result<String>>) -> io::Result<Vec<String>> {
+    let mut commands = Vec::new();
+    for line in lines {
+        let line = line?;
+        let command = line.trim();
+        if !command.is_empty() && !command.starts_with('#') {
+            commands.push(command.to_string());
+        }
+    }
+    Ok(commands)
+}
+
+/// Commands from the arguments, then from the file; stdin is read when both are missing
+pub fn read_commands(args: Vec<String>, file: Option<&Path>) -> io::Result<Vec<String>> {
+    let mut commands = args;
+    if let Some(file) = file {
+        commands.extend(parse_lines(fs::read_to_string(file)?.lines().map(|l| Ok(l.into())))?);
+    } else if commands.is_empty() {
+        commands = parse_lines(io::stdin().lock().lines())?;
+    }
+    Ok(commands)
+}
+
+/// Runs the commands in order and stops at the first failure
+pub async fn run(
+    connection: &RconConnection,
+    commands: Vec<String>,
+    json: bool,
+) -> CrateResult<()> {
+    for command in commands {
+        let response = connection.exec_command(command.clone()).await?;
+
+        if json {
+            let line = ExecLine { command: &command, response: &response };
+            println!("{}", serde_json::to_string(&line).map_err(Error::server_error)?);
+        } else {
+            println!("{}", strip_colors(&response.output));
+        }
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod exec_test {
+    use super::*;
+
+    #[test]
+    fn skip_blank_lines_and_comments() {
+        let script = "save-off\n\n# flush the world\n  save-all flush  \nsave-on\n";
+        let commands = parse_lines(script.lines().map(|l| Ok(l.to_string()))).unwrap();
+        assert_eq!(commands, vec!["save-off", "save-all flush", "save-on"]);
+    }
+
+    #[test]
+    fn exit_codes_by_error() {
+        assert_eq!(exit_code(&Error::rcon_auth_failed("bad password")), EXIT_AUTH_FAILED);
+        assert_eq!(exit_code(&Error::connection_error("refused")), EXIT_CONNECTION_FAILED);
+        assert_eq!(exit_code(&Error::timeout("no response")), EXIT_CONNECTION_FAILED);
+        assert_eq!(exit_code(&Error::server_error("json")), 1);
+    }
+}
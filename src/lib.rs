@@ -1,5 +1,25 @@
-mod password;
-mod user;
-mod rcon;
-mod error;
-mod web_server;
+//! Minecraft RCON client and the web server of the `mc-phone` binary built on it.
+
+pub mod error;
+mod clock;
+pub mod color;
+mod auth;
+mod audit;
+pub mod commands;
+pub mod rcon;
+pub mod pool;
+pub mod query;
+pub mod ping;
+pub mod password;
+mod permission;
+pub mod user;
+pub mod registry;
+mod token;
+mod session;
+pub mod console;
+pub mod exec;
+pub mod admin;
+pub mod config;
+mod tls;
+mod ui;
+pub mod web_server;
@@ -1,27 +1,71 @@
-use std::{io::{self}, sync::{Arc}};
-use clap::{Command, arg};
+use std::{io::{self}, path::PathBuf, sync::{Arc}};
+use clap::{Arg, ArgMatches, Command, arg};
 
-mod error;
-mod rcon;
-mod password;
-mod user;
+use sqlx::SqlitePool;
 
-use error::*;
+use mc_phone::{
+    admin,
+    config::{self, Config},
+    console,
+    error::CrateResult,
+    exec,
+    password::PasswordManager,
+    ping,
+    rcon::RconConnection,
+    registry::{ServerRegistry, DEFAULT_SERVER},
+    user::UserManager,
+    web_server::run_server,
+};
 
-mod web_server;
-use sqlx::SqlitePool;
-use web_server::run_server;
+async fn open_database(config: &Config) -> SqlitePool {
+    let pool = SqlitePool::connect(&config.database.url).await.unwrap();
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("should be migrate before run server");
+    pool
+}
+
+/// RCON address of the console and exec subcommands, the `[[servers]]` of the config
+/// fill the missing ones
+fn rcon_args() -> [Arg; 4] {
+    [
+        arg!(--server <NAME> "server of the config file")
+            .env("RCON_SERVER")
+            .default_value(DEFAULT_SERVER),
+        arg!(--host <HOST>)
+            .env("RCON_HOST"),
+        arg!(--port <PORT>)
+            .env("RCON_PORT")
+            .value_parser(clap::value_parser!(u16)),
+        arg!(--password <PASSWORD>)
+            .env("RCON_PASS"),
+    ]
+}
+
+/// Prints every problem of the config and exits when there is any
+fn exit_if_invalid(config: &Config) {
+    let problems = config.validate();
+    for problem in &problems {
+        eprintln!("{problem}");
+    }
+    if !problems.is_empty() {
+        std::process::exit(1);
+    }
+}
 
-use crate::{password::PasswordManager, rcon::RconConnection};
-use crate::user::UserManager;
+async fn connect_rcon(config: &Config, sub_matches: &ArgMatches) -> CrateResult<RconConnection> {
+    let name = sub_matches.get_one::<String>("server").expect("has default");
+    let target = config.rcon_target(name, sub_matches)?;
+    
+    RconConnection::connect(format!("{}:{}", target.host, target.port), &target.password).await
+}
 
 #[tokio::main]
 async fn main() -> io::Result<()> {
-    env_logger::init();
-    
-    
     let cmd = clap::Command::new("mc-phone")
         .about("cli tools to connection with RCON")
+        .args(config::global_args())
         .subcommand(
             Command::new("server")
                 .about("init web server to connect with RCON")                
@@ -33,6 +77,7 @@ async fn main() -> io::Result<()> {
                 .arg(
                     arg!(--port <PORT>)
                         .env("RCON_PORT")
+                        .value_parser(clap::value_parser!(u16))
                         .num_args(1)
                 )
                 .arg(
@@ -50,39 +95,105 @@ async fn main() -> io::Result<()> {
                         .env("ROOT_PASSWORD")
                         .num_args(1)
                 )
+                .args(config::http_args())
                 .arg_required_else_help(true), 
+        )
+        .subcommand(
+            Command::new("console")
+                .about("open an interactive RCON console")
+                .args(rcon_args()),
+        )
+        .subcommand(
+            Command::new("exec")
+                .about("run RCON commands and print the responses")
+                .args(rcon_args())
+                .arg(arg!(--json "print each response as a JSON line"))
+                .arg(
+                    arg!(--file <FILE> "file with one command per line")
+                        .value_parser(clap::value_parser!(PathBuf))
+                )
+                .arg(arg!([COMMAND] ... "commands to run, read from stdin when missing")),
+        )
+        .subcommand(
+            Command::new("ping")
+                .about("show the status of a server with the Server List Ping")
+                .arg(
+                    arg!(<ADDR> "host[:port] of the game, port 25565 when missing")
+                        .value_parser(|addr: &str| {
+                            ping::parse_address(addr).ok_or("expected host[:port]")
+                        })
+                )
+                .arg(arg!(--json "print the status as JSON")),
+        )
+        .subcommand(admin::user_command())
+        .subcommand(admin::perm_command())
+        .subcommand(
+            Command::new("config")
+                .about("inspect the config file")
+                .subcommand(Command::new("check").about("validate the config file"))
+                .subcommand_required(true),
         );
     
-    match cmd.get_matches().subcommand() {
-        Some(("server", sub_matches)) => {
+    let matches = cmd.get_matches();
+    let Some((subcommand, sub_matches)) = matches.subcommand() else {
+        println!("not implemented");
+        return Ok(());
+    };
+    
+    let config_file = sub_matches.get_one::<PathBuf>("config").map(PathBuf::as_path);
+    // ping only needs its address, a broken config file shouldn't stop it
+    let loaded = match subcommand {
+        "ping" => Ok(Config::default()),
+        _ => Config::load(config_file),
+    };
+    let mut config = match loaded {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        },
+    };
+    if subcommand == "config" {
+        // only the file is checked, flags and environment are left out
+        exit_if_invalid(&config);
+        println!("config is valid");
+        return Ok(());
+    }
+    
+    config.apply_args(sub_matches);
+    env_logger::Builder::new().parse_filters(&config.log.level).init();
+    
+    match (subcommand, sub_matches) {
+        ("server", sub_matches) => {
             let secret_key = sub_matches
                 .get_one::<String>("secret_key")
                 .expect("can't get secret-key");
-            let host = sub_matches
-                .get_one::<String>("host")
-                .expect("can't get host");
-            let port = sub_matches
-                .get_one::<String>("port")
-                .expect("can't get host");
-            let password = sub_matches
-                .get_one::<String>("password")
-                .expect("can't get password");
             let root_password = sub_matches
                 .get_one::<String>("root_password")
                 .expect("can't get root-password");
             
-            let pool = SqlitePool::connect("sqlite://mc-phone.db?mode=rwc").await.unwrap();            
+            if let Err(err) = config.apply_default_server(sub_matches) {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+            exit_if_invalid(&config);
+            
+            let pool = open_database(&config).await;
             let secret_arc = Arc::new(secret_key.clone());
             let password_manager = PasswordManager::new(Arc::new(pool.clone()), Arc::clone(&secret_arc));
             
-            sqlx::migrate!("./migrations")
-                .run(&pool)
-                .await
-                .expect("should be migrate before run server");
-            
-            let rcon = RconConnection::
-                connect(format!("{}:{}", host, port), password.as_str())
-                .await.unwrap();
+            let registry = ServerRegistry::new(Arc::new(pool.clone()), config.rcon.pool());
+            for target in config.servers {
+                let (name, host, port) = (target.name.clone(), target.host.clone(), target.port);
+                if let Err(err) = registry.add_server(target).await {
+                    eprintln!("can't connect to RCON {} at {}:{}: {}", name, host, port, err);
+                    std::process::exit(1);
+                }
+            }
+            if let Err(err) = registry.load().await {
+                eprintln!("can't connect to the stored RCON servers: {}", err);
+                std::process::exit(1);
+            }
             
             let user_manager = UserManager::new(Arc::new(pool.clone()));
             
@@ -92,12 +203,88 @@ async fn main() -> io::Result<()> {
             run_server(
                 pool.clone(),
                 password_manager,
-                rcon,
+                registry,
                 user_manager,
+                secret_arc,
+                config.http,
+                config.tls,
             ).await.unwrap();
             
             Ok(())
         },
+        ("console", sub_matches) => {
+            let connection = match connect_rcon(&config, sub_matches).await {
+                Ok(connection) => connection,
+                Err(err) => {
+                    eprintln!("can't connect to RCON: {err}");
+                    std::process::exit(1);
+                },
+            };
+            if let Err(err) = console::run(connection).await {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+            
+            Ok(())
+        },
+        ("exec", sub_matches) => {
+            let args = sub_matches.get_many::<String>("COMMAND").unwrap_or_default().cloned();
+            let file = sub_matches.get_one::<PathBuf>("file").map(PathBuf::as_path);
+            let commands = match exec::read_commands(args.collect(), file) {
+                Ok(commands) => commands,
+                Err(err) => {
+                    eprintln!("can't read the commands: {err}");
+                    std::process::exit(1);
+                },
+            };
+            let connection = match connect_rcon(&config, sub_matches).await {
+                Ok(connection) => connection,
+                Err(err) => {
+                    eprintln!("can't connect to RCON: {err}");
+                    std::process::exit(exec::exit_code(&err));
+                },
+            };
+            
+            let json = sub_matches.get_flag("json");
+            if let Err(err) = exec::run(&connection, commands, json).await {
+                eprintln!("{err}");
+                std::process::exit(exec::exit_code(&err));
+            }
+            
+            Ok(())
+        },
+        ("ping", sub_matches) => {
+            let (host, port) = sub_matches.get_one::<(String, u16)>("ADDR").expect("required");
+            let result = ping::ping(host, *port).await;
+            
+            let json = sub_matches.get_flag("json");
+            if let Err(err) = result.and_then(|result| ping::print(&result, json)) {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+            
+            Ok(())
+        },
+        ("user", sub_matches) => {
+            let pool = Arc::new(open_database(&config).await);
+            
+            if let Err(err) = admin::run_user(sub_matches, pool).await {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+            
+            Ok(())
+        },
+        ("perm", sub_matches) => {
+            let pool = Arc::new(open_database(&config).await);
+            
+            if let Err(err) = admin::run_perm(sub_matches, pool).await {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+            
+            Ok(())
+        },
         _ => {
             println!("not implemented");
             Ok(())
@@ -43,11 +43,10 @@ impl PassHasher {
         Ok(password_hash)
     }
     
-    fn verify_password<'c>(&self, password: String, hash: &'c str) -> Result<(), ()> {
-        if let Ok(hashed) = PasswordHash::new(hash) {
-            if let Ok(_) = self.hash_algo.verify_password(password.as_bytes(), &hashed) {
-                return Ok(())
-            }
+    fn verify_password(&self, password: String, hash: &str) -> Result<(), ()> {
+        if let Ok(hashed) = PasswordHash::new(hash)
+            && self.hash_algo.verify_password(password.as_bytes(), &hashed).is_ok() {
+            return Ok(())
         }
         
         Err(())
@@ -59,21 +58,22 @@ impl PassHasher {
 pub struct PasswordManager {
     pool: Arc<SqlitePool>,
     hasher: PassHasher,
+    /// Verified instead of the hash of an unknown nick, so the answer takes as long
+    dummy_hash: Arc<String>,
 }
 
 impl PasswordManager {
     
-    pub(crate) fn new(pool: Arc<SqlitePool>, secret_key: Arc<String>) -> Self {
-        Self { pool, hasher: PassHasher::new(secret_key) }
+    pub fn new(pool: Arc<SqlitePool>, secret_key: Arc<String>) -> Self {
+        let hasher = PassHasher::new(secret_key);
+        let dummy_hash = Arc::new(hasher.hash_password(String::new()).unwrap());
+        Self { pool, hasher, dummy_hash }
     }
     
-    pub(crate) fn hash_password(&self, password: String) -> Result<String, ()> {
-        let password_hash = self.hasher
+    pub fn hash_password(&self, password: String) -> CrateResult<String> {
+        self.hasher
             .hash_password(password)
-            .unwrap()
-            .to_string();
-        
-        Ok(password_hash)
+            .map_err(|_| Error::server_error("can't hash password"))
     }
     
     pub(crate) async fn verify_user_password(
@@ -81,17 +81,21 @@ impl PasswordManager {
         user_nick: String, 
         password: String
     ) -> CrateResult<()> {
-        let row: (String,) = sqlx::query_as("SELECT password FROM rcon_users u WHERE u.game_nick = $1")
-            .bind(user_nick)
-            .fetch_one(Arc::as_ref(&self.pool))
+        let row: Option<(String, bool)> = sqlx::query_as(
+            "SELECT password, disabled FROM rcon_users u WHERE u.game_nick = $1")
+            .bind(&user_nick)
+            .fetch_optional(Arc::as_ref(&self.pool))
             .await
-            .unwrap();
+            .map_err(Error::server_error)?;
         
-        match self.hasher.verify_password(password, &row.0) {
-            Ok(_) => Ok(()),
-            Err(_) => {
-                Err(Error::PasswordDontMatch{ raw_err: "password is invalid".to_string() })
-            }
+        // unknown nicks are hashed too, and the disabled flag is only told to who knows
+        // the password
+        let hash = row.as_ref().map_or(self.dummy_hash.as_str(), |(hash, _)| hash.as_str());
+        let verified = self.hasher.verify_password(password, hash).is_ok();
+        match row {
+            Some((_, true)) if verified => Err(Error::user_disabled(user_nick)),
+            Some(_) if verified => Ok(()),
+            _ => Err(Error::PasswordDontMatch{ raw_err: "password is invalid".to_string() }),
         }
     }
 }
@@ -101,7 +105,7 @@ impl PasswordManager {
 mod hasher_test {
     use super::*;
     
-    const DUMB_SECRET: &'static str = "@test-secret123";
+    const DUMB_SECRET: &str = "@test-secret123";
     
     #[test]
     fn hash_and_verify_pass() {
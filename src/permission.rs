@@ -0,0 +1,84 @@
+//! Permission entries are patterns over the whole command line:
+//!
+//! - a literal token must be equal to the command token
+//! - `{self}` is bound to the `game_nick` of the caller
+//! - `*` matches any single token, as the last token it matches all the remaining ones
+//! - a pattern with only the command name, like `say`, allows any arguments
+
+/// Placeholder replaced by the nick of the caller
+pub(crate) const SELF_TOKEN: &str = "{self}";
+const WILDCARD_TOKEN: &str = "*";
+
+fn tokens(line: &str) -> Vec<&str> {
+    line.trim().trim_start_matches('/').split_whitespace().collect()
+}
+
+/// Tells if `pattern` allows `nick` to run `command_line`
+pub(crate) fn matches(pattern: &str, command_line: &str, nick: &str) -> bool {
+    let pattern = tokens(pattern);
+    let command = tokens(command_line);
+
+    let (Some(name), Some(command_name)) = (pattern.first(), command.first()) else {
+        return false;
+    };
+    if !token_matches(name, command_name, nick) {
+        return false;
+    }
+    if pattern.len() == 1 {
+        return true;
+    }
+
+    for (i, expected) in pattern.iter().enumerate().skip(1) {
+        let Some(actual) = command.get(i) else {
+            return false;
+        };
+        if *expected == WILDCARD_TOKEN && i == pattern.len() - 1 {
+            return true;
+        }
+        if !token_matches(expected, actual, nick) {
+            return false;
+        }
+    }
+
+    pattern.len() == command.len()
+}
+
+fn token_matches(expected: &str, actual: &str, nick: &str) -> bool {
+    match expected {
+        WILDCARD_TOKEN => true,
+        // Minecraft nicks are case insensitive
+        SELF_TOKEN => actual.eq_ignore_ascii_case(nick),
+        literal => literal == actual,
+    }
+}
+
+
+#[cfg(test)]
+mod permission_test {
+    use super::*;
+
+    #[test]
+    fn command_name_allows_any_arguments() {
+        assert!(matches("say", "/say hello world", "steve"));
+        assert!(matches("list", "/list", "steve"));
+        assert!(!matches("say", "/me hello", "steve"));
+    }
+
+    #[test]
+    fn self_is_bound_to_caller() {
+        assert!(matches("gamemode creative {self}", "/gamemode creative Steve", "steve"));
+        assert!(!matches("gamemode creative {self}", "/gamemode creative alex", "steve"));
+        assert!(!matches("gamemode creative {self}", "/gamemode creative", "steve"));
+        assert!(!matches("gamemode creative {self}", "/gamemode creative steve alex", "steve"));
+    }
+
+    #[test]
+    fn trailing_wildcard_matches_the_rest() {
+        assert!(matches("tp {self} *", "/tp steve alex", "steve"));
+        assert!(matches("tp {self} *", "/tp steve 10 64 -20", "steve"));
+        assert!(!matches("tp {self} *", "/tp steve", "steve"));
+        assert!(!matches("tp {self} *", "/tp alex steve", "steve"));
+        assert!(matches("whitelist add *", "/whitelist add alex", "steve"));
+        assert!(!matches("whitelist add *", "/whitelist remove alex", "steve"));
+    }
+}
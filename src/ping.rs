@@ -0,0 +1,347 @@
+//! Server List Ping, the status request answered by every Java server on its game port.
+//!
+//! Packets are framed by a VarInt length followed by a VarInt packet id. The client sends
+//! a handshake switching to the status state, a status request answered with JSON, and a
+//! ping whose pong gives the latency.
+
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use crate::{
+    color::strip_colors,
+    error::{CrateResult, Error},
+};
+
+/// Default `server-port` of server.properties
+pub const DEFAULT_GAME_PORT: u16 = 25565;
+
+/// Any version is accepted by the status state, -1 is used when the client doesn't care
+const PROTOCOL_VERSION: i32 = -1;
+const STATUS_STATE: i32 = 1;
+const HANDSHAKE_ID: i32 = 0x00;
+const STATUS_ID: i32 = 0x00;
+const PING_ID: i32 = 0x01;
+/// The status JSON with a favicon is a few KiB, anything much bigger is not a server
+const MAX_PACKET_SIZE: usize = 1024 * 1024;
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn write_varint(buffer: &mut Vec<u8>, value: i32) {
+    let mut value = value as u32;
+    loop {
+        if value & !0x7F == 0 {
+            buffer.push(value as u8);
+            return;
+        }
+        buffer.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+}
+
+/// Reads a VarInt from the start of `data` and advances it
+fn read_varint(data: &mut &[u8]) -> CrateResult<i32> {
+    let mut value = 0u32;
+    for i in 0..5 {
+        let (&byte, rest) = data
+            .split_first()
+            .ok_or_else(|| Error::connection_error("truncated VarInt"))?;
+        *data = rest;
+        value |= ((byte & 0x7F) as u32) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(value as i32);
+        }
+    }
+    Err(Error::connection_error("VarInt is longer than 5 bytes"))
+}
+
+fn write_string(buffer: &mut Vec<u8>, value: &str) {
+    write_varint(buffer, value.len() as i32);
+    buffer.extend(value.as_bytes());
+}
+
+fn packet(id: i32, payload: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(payload.len() + 1);
+    write_varint(&mut body, id);
+    body.extend(payload);
+
+    let mut packet = Vec::with_capacity(body.len() + 5);
+    write_varint(&mut packet, body.len() as i32);
+    packet.extend(body);
+    packet
+}
+
+/// Reads one packet and returns its payload, failing when the id isn't `expected_id`
+async fn read_packet(stream: &mut TcpStream, expected_id: i32) -> CrateResult<Vec<u8>> {
+    let mut length = 0u32;
+    for i in 0..5 {
+        let byte = stream.read_u8().await.map_err(Error::connection_error)?;
+        length |= ((byte & 0x7F) as u32) << (7 * i);
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    let length = length as usize;
+    if length == 0 || length > MAX_PACKET_SIZE {
+        return Err(Error::connection_error(format!("invalid packet length {length}")));
+    }
+
+    let mut body = vec![0; length];
+    stream.read_exact(&mut body).await.map_err(Error::connection_error)?;
+
+    let mut data = body.as_slice();
+    let id = read_varint(&mut data)?;
+    if id != expected_id {
+        return Err(Error::connection_error(format!("expected packet {expected_id}, got {id}")));
+    }
+    Ok(data.to_vec())
+}
+
+/// Chat component of the MOTD, either plain text or objects with nested `extra` parts
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum TextComponent {
+    Text(String),
+    List(Vec<TextComponent>),
+    Object {
+        #[serde(default)]
+        text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        color: Option<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        extra: Vec<TextComponent>,
+    },
+}
+
+impl TextComponent {
+    /// Text of every part without colors nor `§` codes
+    pub fn to_plain(&self) -> String {
+        let mut plain = String::new();
+        self.push_plain(&mut plain);
+        strip_colors(&plain)
+    }
+
+    fn push_plain(&self, plain: &mut String) {
+        match self {
+            Self::Text(text) => plain.push_str(text),
+            Self::List(parts) => parts.iter().for_each(|part| part.push_plain(plain)),
+            Self::Object { text, extra, .. } => {
+                plain.push_str(text);
+                extra.iter().for_each(|part| part.push_plain(plain));
+            },
+        }
+    }
+}
+
+impl Default for TextComponent {
+    fn default() -> Self {
+        Self::Text(String::new())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Version {
+    pub name: String,
+    pub protocol: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PlayerSample {
+    pub name: String,
+    pub id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Players {
+    pub max: u32,
+    pub online: u32,
+    /// Some of the online players, servers may hide them
+    #[serde(default)]
+    pub sample: Vec<PlayerSample>,
+}
+
+/// JSON answered to the status request
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ServerStatus {
+    pub version: Version,
+    /// Missing when the server hides its player count
+    pub players: Option<Players>,
+    #[serde(default)]
+    pub description: TextComponent,
+    /// `data:image/png;base64,` URI of the 64x64 server icon
+    pub favicon: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PingResult {
+    #[serde(flatten)]
+    pub status: ServerStatus,
+    /// Round trip of the ping packet
+    pub latency_ms: u64,
+}
+
+async fn status(host: &str, port: u16) -> CrateResult<PingResult> {
+    let mut stream = TcpStream::connect((host, port)).await.map_err(Error::connection_error)?;
+
+    let mut handshake = Vec::new();
+    write_varint(&mut handshake, PROTOCOL_VERSION);
+    write_string(&mut handshake, host);
+    handshake.extend(port.to_be_bytes());
+    write_varint(&mut handshake, STATUS_STATE);
+
+    let mut request = packet(HANDSHAKE_ID, &handshake);
+    request.extend(packet(STATUS_ID, &[]));
+    stream.write_all(&request).await.map_err(Error::connection_error)?;
+
+    let response = read_packet(&mut stream, STATUS_ID).await?;
+    let mut data = response.as_slice();
+    let length = read_varint(&mut data)? as usize;
+    let json = data
+        .get(..length)
+        .ok_or_else(|| Error::connection_error("truncated status response"))?;
+    let status: ServerStatus = serde_json::from_slice(json)
+        .map_err(|err| Error::connection_error(format!("invalid status JSON: {err}")))?;
+
+    // vanilla clients send a timestamp, the server only echoes it
+    let payload = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_millis() as i64);
+    let started = Instant::now();
+    stream
+        .write_all(&packet(PING_ID, &payload.to_be_bytes()))
+        .await
+        .map_err(Error::connection_error)?;
+    let pong = read_packet(&mut stream, PING_ID).await?;
+    if pong != payload.to_be_bytes() {
+        return Err(Error::connection_error("pong payload doesn't match the ping"));
+    }
+
+    Ok(PingResult { status, latency_ms: started.elapsed().as_millis() as u64 })
+}
+
+/// Status and latency of the server at `host:port`
+pub async fn ping(host: &str, port: u16) -> CrateResult<PingResult> {
+    tokio::time::timeout(RESPONSE_TIMEOUT, status(host, port))
+        .await
+        .map_err(|_| Error::connection_error(format!("{host}:{port} didn't answer the ping")))?
+}
+
+/// `host`, `host:port` or `[ipv6]:port`, the port defaults to [`DEFAULT_GAME_PORT`]
+pub fn parse_address(addr: &str) -> Option<(String, u16)> {
+    let (host, port) = match addr.rsplit_once(':') {
+        // a bare IPv6 address has colons without a port
+        Some((host, _)) if host.contains(':') && !host.ends_with(']') => (addr, None),
+        Some((host, port)) => (host, Some(port.parse().ok()?)),
+        None => (addr, None),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() {
+        return None;
+    }
+    Some((host.to_string(), port.unwrap_or(DEFAULT_GAME_PORT)))
+}
+
+/// Output of `mc-phone ping`
+pub fn print(result: &PingResult, json: bool) -> CrateResult<()> {
+    if json {
+        println!("{}", serde_json::to_string(result).map_err(Error::server_error)?);
+        return Ok(());
+    }
+
+    let status = &result.status;
+    println!("{}", status.description.to_plain());
+    println!("version: {} (protocol {})", status.version.name, status.version.protocol);
+    match &status.players {
+        Some(players) => {
+            let names: Vec<&str> = players.sample.iter().map(|p| p.name.as_str()).collect();
+            println!("players: {}/{} {}", players.online, players.max, names.join(", "));
+        },
+        None => println!("players: hidden"),
+    }
+    println!("latency: {} ms", result.latency_ms);
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod ping_test {
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    const STATUS: &str = r#"{
+        "version": {"name": "1.21.1", "protocol": 767},
+        "players": {
+            "max": 20,
+            "online": 1,
+            "sample": [{"name": "Steve", "id": "8667ba71-b85a-4004-af54-457a9734eed7"}]
+        },
+        "description": {"text": "§aHello ", "extra": [{"text": "world", "color": "gold"}]},
+        "favicon": "data:image/png;base64,AAAA",
+        "enforcesSecureChat": true
+    }"#;
+
+    /// Answers the status request and echoes the ping like a Java server
+    async fn fake_server() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            let handshake = read_packet(&mut stream, HANDSHAKE_ID).await.unwrap();
+            let mut data = handshake.as_slice();
+            assert_eq!(read_varint(&mut data).unwrap(), PROTOCOL_VERSION);
+            assert!(read_packet(&mut stream, STATUS_ID).await.unwrap().is_empty());
+
+            let mut response = Vec::new();
+            write_string(&mut response, STATUS);
+            stream.write_all(&packet(STATUS_ID, &response)).await.unwrap();
+
+            let ping = read_packet(&mut stream, PING_ID).await.unwrap();
+            stream.write_all(&packet(PING_ID, &ping)).await.unwrap();
+        });
+
+        port
+    }
+
+    #[test]
+    fn varint_round_trip() {
+        for (value, encoded) in [
+            (0, vec![0x00]),
+            (300, vec![0xAC, 0x02]),
+            (2_147_483_647, vec![0xFF, 0xFF, 0xFF, 0xFF, 0x07]),
+            (-1, vec![0xFF, 0xFF, 0xFF, 0xFF, 0x0F]),
+        ] {
+            let mut buffer = Vec::new();
+            write_varint(&mut buffer, value);
+            assert_eq!(buffer, encoded);
+            assert_eq!(read_varint(&mut buffer.as_slice()).unwrap(), value);
+        }
+        assert!(read_varint(&mut [0xFF; 6].as_slice()).is_err());
+    }
+
+    #[tokio::test]
+    async fn ping_reads_status_and_latency() {
+        let port = fake_server().await;
+        let result = ping("127.0.0.1", port).await.unwrap();
+
+        let status = result.status;
+        assert_eq!(status.version.name, "1.21.1");
+        assert_eq!(status.players.as_ref().unwrap().sample[0].name, "Steve");
+        assert_eq!(status.description.to_plain(), "Hello world");
+        assert!(status.favicon.is_some());
+    }
+
+    #[test]
+    fn parse_addresses() {
+        assert_eq!(parse_address("mc.local"), Some(("mc.local".to_string(), 25565)));
+        assert_eq!(parse_address("mc.local:25566"), Some(("mc.local".to_string(), 25566)));
+        assert_eq!(parse_address("[::1]:25566"), Some(("::1".to_string(), 25566)));
+        assert_eq!(parse_address("::1"), Some(("::1".to_string(), 25565)));
+        assert!(parse_address("mc.local:port").is_none());
+    }
+}
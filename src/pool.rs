@@ -0,0 +1,330 @@
+//! Several authenticated RCON connections to the same server.
+//!
+//! Minecraft runs the commands of one socket one after the other, so a burst of
+//! commands only goes as fast as the number of sockets it is spread over. The first
+//! connection is kept open and reconnected by its supervisor, the other ones are opened
+//! when every open connection is busy and closed once they stay idle.
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Weak,
+    },
+    time::{Duration, Instant},
+};
+
+use tokio::sync::{watch, Mutex};
+
+use crate::{
+    error::{CrateResult, Error},
+    rcon::{ConnectionState, RconConnection, RconResponse, ReconnectPolicy, Timeouts},
+};
+
+/// Limits of a [`RconPool`]
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    /// Most connections opened at once, at least 1
+    pub size: usize,
+    /// Connections besides the first one are closed after being unused this long
+    pub idle_timeout: Duration,
+    /// Wait between two pings of the idle connections
+    pub health_interval: Duration,
+    /// Timeouts of every connection, a ping fails after [`Timeouts::command`]
+    pub timeouts: Timeouts,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            size: 4,
+            idle_timeout: Duration::from_secs(60),
+            health_interval: Duration::from_secs(30),
+            timeouts: Timeouts::default(),
+        }
+    }
+}
+
+struct Slot {
+    /// Locked while the connection is being opened, the other callers skip the slot
+    conn: Mutex<Option<Arc<RconConnection>>>,
+    /// Commands running on the connection
+    in_flight: AtomicUsize,
+    last_used: std::sync::Mutex<Instant>,
+}
+
+/// Connection handed out for one command, the slot counts it as busy until it is dropped
+struct Lease<'a> {
+    slot: &'a Slot,
+    conn: Arc<RconConnection>,
+}
+
+impl<'a> Lease<'a> {
+    fn new(slot: &'a Slot, conn: Arc<RconConnection>) -> Self {
+        slot.in_flight.fetch_add(1, Ordering::Relaxed);
+        Self { slot, conn }
+    }
+}
+
+impl Drop for Lease<'_> {
+    fn drop(&mut self) {
+        *self.slot.last_used.lock().unwrap() = Instant::now();
+        self.slot.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+struct Inner {
+    addr: String,
+    password: String,
+    policy: ReconnectPolicy,
+    config: PoolConfig,
+    /// The first slot always holds the primary connection
+    slots: Vec<Slot>,
+    primary: Arc<RconConnection>,
+    /// Slot where the next search starts, so ties go round robin
+    next: AtomicUsize,
+}
+
+/// Up to [`PoolConfig::size`] RCON connections to the same server, each command runs on
+/// the least busy one.
+pub struct RconPool {
+    inner: Arc<Inner>,
+}
+
+impl RconPool {
+    /// Fails like [`RconConnection::connect`] when the primary connection can't be opened
+    pub async fn connect<A: Into<String>>(
+        addr: A,
+        pass: &str,
+        policy: ReconnectPolicy,
+        config: PoolConfig,
+    ) -> CrateResult<Self> {
+        let addr = addr.into();
+        let timeouts = config.timeouts.clone();
+        let primary =
+            RconConnection::connect_with_policy(addr.clone(), pass, policy.clone(), timeouts)
+                .await?;
+        Ok(Self::new(addr, pass, policy, config, primary))
+    }
+
+    /// Returns without waiting the server, like [`RconConnection::spawn`]
+    pub fn spawn<A: Into<String>>(
+        addr: A,
+        pass: &str,
+        policy: ReconnectPolicy,
+        config: PoolConfig,
+    ) -> Self {
+        let addr = addr.into();
+        let timeouts = config.timeouts.clone();
+        let primary = RconConnection::spawn(addr.clone(), pass, policy.clone(), timeouts);
+        Self::new(addr, pass, policy, config, primary)
+    }
+
+    fn new(
+        addr: String,
+        pass: &str,
+        policy: ReconnectPolicy,
+        config: PoolConfig,
+        primary: RconConnection,
+    ) -> Self {
+        let primary = Arc::new(primary);
+        let slots = (0..config.size.max(1))
+            .map(|i| Slot {
+                conn: Mutex::new((i == 0).then(|| Arc::clone(&primary))),
+                in_flight: AtomicUsize::new(0),
+                last_used: std::sync::Mutex::new(Instant::now()),
+            })
+            .collect();
+        let inner = Arc::new(Inner {
+            addr,
+            password: pass.to_string(),
+            policy,
+            config,
+            slots,
+            primary,
+            next: AtomicUsize::new(0),
+        });
+        tokio::spawn(Self::check_health(Arc::downgrade(&inner)));
+
+        Self { inner }
+    }
+
+    /// State of the primary connection, the other ones only exist while it is connected
+    pub fn state(&self) -> ConnectionState {
+        self.inner.primary.state()
+    }
+
+    /// Receives every change of the state of the primary connection
+    pub fn subscribe(&self) -> watch::Receiver<ConnectionState> {
+        self.inner.primary.subscribe()
+    }
+
+    /// Number of connections currently open, the ones being opened are not counted yet
+    pub fn open_connections(&self) -> usize {
+        self.inner
+            .slots
+            .iter()
+            .filter(|slot| slot.conn.try_lock().is_ok_and(|conn| conn.is_some()))
+            .count()
+    }
+
+    /// Runs the command on the least busy connection
+    pub async fn exec_command(&self, cmd: String) -> CrateResult<RconResponse> {
+        let lease = self.acquire().await?;
+        lease.conn.exec_command(cmd).await
+    }
+
+    /// Picks an idle connection, or opens a new one when every connection is busy, or
+    /// falls back to the least busy one once the pool is full
+    async fn acquire(&self) -> CrateResult<Lease<'_>> {
+        let inner = &self.inner;
+        let len = inner.slots.len();
+        let start = inner.next.fetch_add(1, Ordering::Relaxed) % len;
+
+        let mut least_busy: Option<(&Slot, Arc<RconConnection>, usize)> = None;
+        let mut empty = None;
+        for slot in (0..len).map(|i| &inner.slots[(start + i) % len]) {
+            // a slot being opened is left to the caller opening it
+            let Ok(conn) = slot.conn.try_lock() else {
+                continue;
+            };
+            match conn.as_ref() {
+                Some(conn) if conn.state() == ConnectionState::Connected => {
+                    let busy = slot.in_flight.load(Ordering::Relaxed);
+                    if busy == 0 {
+                        return Ok(Lease::new(slot, Arc::clone(conn)));
+                    }
+                    if least_busy.as_ref().is_none_or(|(_, _, least)| busy < *least) {
+                        least_busy = Some((slot, Arc::clone(conn), busy));
+                    }
+                },
+                Some(_) => {},
+                None => empty = empty.or(Some(slot)),
+            }
+        }
+
+        // new sockets are only worth it while the server answers on the primary one
+        if let Some(slot) = empty
+            && inner.primary.state() == ConnectionState::Connected
+            && let Ok(mut conn) = slot.conn.try_lock()
+            && conn.is_none()
+        {
+            let opened = RconConnection::connect_with_policy(
+                inner.addr.clone(),
+                &inner.password,
+                inner.policy.clone(),
+                inner.config.timeouts.clone(),
+            ).await;
+            match opened {
+                Ok(opened) => {
+                    let opened = Arc::new(opened);
+                    *conn = Some(Arc::clone(&opened));
+                    return Ok(Lease::new(slot, opened));
+                },
+                Err(err) => log::warn!("can't open a pooled RCON connection: {}", err),
+            }
+        }
+
+        match least_busy {
+            Some((slot, conn, _)) => Ok(Lease::new(slot, conn)),
+            None => Err(Error::rcon_unavailable(format!("{:?}", self.state()))),
+        }
+    }
+
+    /// Background task pinging the idle connections and closing the ones unused for
+    /// [`PoolConfig::idle_timeout`], it stops once the pool is dropped.
+    async fn check_health(inner: Weak<Inner>) {
+        loop {
+            let Some(interval) = inner.upgrade().map(|inner| inner.config.health_interval) else {
+                return;
+            };
+            tokio::time::sleep(interval).await;
+            let Some(inner) = inner.upgrade() else {
+                return;
+            };
+
+            for (i, slot) in inner.slots.iter().enumerate() {
+                let Some(conn) = slot.conn.try_lock().ok().and_then(|conn| conn.clone()) else {
+                    continue;
+                };
+                if slot.in_flight.load(Ordering::Relaxed) > 0 {
+                    continue;
+                }
+
+                let idle = slot.last_used.lock().unwrap().elapsed() >= inner.config.idle_timeout;
+                let healthy =
+                    conn.state() == ConnectionState::Connected && conn.ping().await.is_ok();
+                if i == 0 {
+                    // the supervisor of the primary connection replaces the socket
+                    if !healthy && conn.state() == ConnectionState::Connected {
+                        conn.reconnect(&Error::connection_error("no reply to the ping")).await;
+                    }
+                } else if idle || !healthy {
+                    slot.conn.lock().await.take_if(|current| Arc::ptr_eq(current, &conn));
+                }
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod pool_test {
+    use super::*;
+    use crate::rcon::fake_server::{eventually, FakeServer, PASSWORD};
+
+    fn config(size: usize) -> PoolConfig {
+        PoolConfig {
+            size,
+            idle_timeout: Duration::from_millis(50),
+            health_interval: Duration::from_millis(20),
+            timeouts: Timeouts::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn busy_connections_open_new_ones() {
+        let server = FakeServer::start("", 4096).await;
+        let pool = RconPool::connect(
+            server.addr.clone(), PASSWORD, ReconnectPolicy::default(), config(3),
+        ).await.map(Arc::new).unwrap();
+
+        let calls: Vec<_> = (0..3)
+            .map(|_| {
+                let pool = Arc::clone(&pool);
+                tokio::spawn(async move { pool.exec_command("/wait".to_string()).await })
+            })
+            .collect();
+
+        // one socket would only run the first wait until it is released
+        eventually(|| server.waiting() == 3).await;
+        assert_eq!(server.sockets(), 3);
+        eventually(|| pool.open_connections() == 3).await;
+
+        server.release(3);
+        for call in calls {
+            assert_eq!(call.await.unwrap().unwrap().output, "released");
+        }
+    }
+
+    #[tokio::test]
+    async fn idle_connections_are_closed() {
+        let server = FakeServer::start("", 4096).await;
+        let pool = RconPool::connect(
+            server.addr.clone(), PASSWORD, ReconnectPolicy::default(), config(2),
+        ).await.map(Arc::new).unwrap();
+
+        let waiting = {
+            let pool = Arc::clone(&pool);
+            tokio::spawn(async move { pool.exec_command("/wait".to_string()).await })
+        };
+        eventually(|| server.waiting() == 1).await;
+        assert_eq!(pool.exec_command("/socket".to_string()).await.unwrap().output, "2");
+        eventually(|| pool.open_connections() == 2).await;
+        server.release(1);
+        waiting.await.unwrap().unwrap();
+
+        eventually(|| pool.open_connections() == 1).await;
+        assert_eq!(pool.exec_command("/socket".to_string()).await.unwrap().output, "1");
+        assert_eq!(server.sockets(), 2);
+    }
+}
@@ -0,0 +1,312 @@
+//! Client of the Query protocol (GameSpy4 over UDP), answered by servers with
+//! `enable-query=true` without any password.
+//!
+//! Every request starts with a handshake returning a challenge token, the server
+//! forgets the tokens after 30 seconds so they aren't reused.
+
+use std::{collections::HashMap, time::Duration};
+
+use serde::Serialize;
+use tokio::net::UdpSocket;
+
+use crate::error::{CrateResult, Error};
+
+/// Default `query.port` of server.properties
+pub const DEFAULT_QUERY_PORT: u16 = 25565;
+
+const MAGIC: [u8; 2] = [0xFE, 0xFD];
+const HANDSHAKE: u8 = 9;
+const STAT: u8 = 0;
+/// The server only accepts the low 4 bits of each byte of the session id
+const SESSION_ID_MASK: i32 = 0x0F0F_0F0F;
+/// "splitnum\0\x80\0" before the key/value section of the full stat
+const FULL_STAT_PADDING: usize = 11;
+/// "\x01player_\0\0" before the player names of the full stat
+const PLAYERS_PADDING: usize = 10;
+/// Responses never span more than one datagram
+const MAX_DATAGRAM_SIZE: usize = 65_507;
+/// UDP has no connection to fail, a disabled query only shows as silence
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Answer of the basic stat request
+#[derive(Debug, PartialEq, Serialize)]
+pub struct BasicStat {
+    pub motd: String,
+    pub game_type: String,
+    pub map: String,
+    pub online_players: u32,
+    pub max_players: u32,
+    pub host_port: u16,
+    pub host_ip: String,
+}
+
+/// Answer of the full stat request
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FullStat {
+    pub motd: String,
+    pub game_type: String,
+    pub game_id: String,
+    pub version: String,
+    /// Name and version of the server software, like `Paper on 1.21.1`
+    pub server_mod: Option<String>,
+    pub plugins: Vec<String>,
+    pub map: String,
+    pub online_players: u32,
+    pub max_players: u32,
+    pub host_port: u16,
+    pub host_ip: String,
+    pub players: Vec<String>,
+}
+
+/// Reads the null terminated strings of a response
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn skip(&mut self, len: usize) -> CrateResult<()> {
+        if self.data.len() < len {
+            return Err(Error::connection_error("query response is too short"));
+        }
+        self.data = &self.data[len..];
+        Ok(())
+    }
+
+    fn string(&mut self) -> CrateResult<String> {
+        let end = self
+            .data
+            .iter()
+            .position(|byte| *byte == 0)
+            .ok_or_else(|| Error::connection_error("query response has an unterminated string"))?;
+        let value = String::from_utf8_lossy(&self.data[..end]).into_owned();
+        self.data = &self.data[end + 1..];
+        Ok(value)
+    }
+
+    fn u16_le(&mut self) -> CrateResult<u16> {
+        let bytes = self.data.get(..2).ok_or_else(|| {
+            Error::connection_error("query response is too short")
+        })?;
+        let value = u16::from_le_bytes([bytes[0], bytes[1]]);
+        self.data = &self.data[2..];
+        Ok(value)
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(field: &str, value: &str) -> CrateResult<T> {
+    value
+        .parse()
+        .map_err(|_| Error::connection_error(format!("query {field} is not a number: {value}")))
+}
+
+/// `ServerMod: Plugin1 1.0; Plugin2 2.0`, empty on vanilla servers
+fn parse_plugins(plugins: &str) -> (Option<String>, Vec<String>) {
+    if plugins.trim().is_empty() {
+        return (None, Vec::new());
+    }
+    let (server_mod, list) = plugins.split_once(':').unwrap_or((plugins, ""));
+    let list = list
+        .split(';')
+        .map(str::trim)
+        .filter(|plugin| !plugin.is_empty())
+        .map(String::from)
+        .collect();
+    (Some(server_mod.trim().to_string()), list)
+}
+
+fn request(kind: u8, session_id: i32, payload: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(7 + payload.len());
+    packet.extend(MAGIC);
+    packet.push(kind);
+    packet.extend(session_id.to_be_bytes());
+    packet.extend(payload);
+    packet
+}
+
+/// Query session bound to one server
+pub struct QueryClient {
+    socket: UdpSocket,
+    session_id: i32,
+}
+
+impl QueryClient {
+    pub async fn connect(addr: &str) -> CrateResult<Self> {
+        let addr = tokio::net::lookup_host(addr)
+            .await
+            .map_err(Error::connection_error)?
+            .next()
+            .ok_or_else(|| Error::connection_error(format!("{addr} has no address")))?;
+        let local = if addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+
+        let socket = UdpSocket::bind(local).await.map_err(Error::connection_error)?;
+        socket.connect(addr).await.map_err(Error::connection_error)?;
+
+        let session_id = std::process::id() as i32 & SESSION_ID_MASK;
+        Ok(Self { socket, session_id })
+    }
+
+    /// Sends a request and returns the payload of the answer, after its type and session id
+    async fn send(&self, kind: u8, payload: &[u8]) -> CrateResult<Vec<u8>> {
+        let packet = request(kind, self.session_id, payload);
+        self.socket.send(&packet).await.map_err(Error::connection_error)?;
+
+        let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+        loop {
+            let len = tokio::time::timeout(RESPONSE_TIMEOUT, self.socket.recv(&mut buffer))
+                .await
+                .map_err(|_| Error::connection_error("no query response, is enable-query on?"))?
+                .map_err(Error::connection_error)?;
+
+            // late answers of a previous request are dropped
+            let response = &buffer[..len];
+            if len >= 5
+                && response[0] == kind
+                && response[1..5] == self.session_id.to_be_bytes()
+            {
+                return Ok(response[5..].to_vec());
+            }
+        }
+    }
+
+    async fn challenge_token(&self) -> CrateResult<i32> {
+        let response = self.send(HANDSHAKE, &[]).await?;
+        let token = Reader { data: &response }.string()?;
+        parse_number("challenge token", &token)
+    }
+
+    pub async fn basic_stat(&self) -> CrateResult<BasicStat> {
+        let token = self.challenge_token().await?;
+        let response = self.send(STAT, &token.to_be_bytes()).await?;
+
+        let mut reader = Reader { data: &response };
+        Ok(BasicStat {
+            motd: reader.string()?,
+            game_type: reader.string()?,
+            map: reader.string()?,
+            online_players: parse_number("numplayers", &reader.string()?)?,
+            max_players: parse_number("maxplayers", &reader.string()?)?,
+            host_port: reader.u16_le()?,
+            host_ip: reader.string()?,
+        })
+    }
+
+    pub async fn full_stat(&self) -> CrateResult<FullStat> {
+        let token = self.challenge_token().await?;
+        let mut payload = token.to_be_bytes().to_vec();
+        // the padding asks for the full stat instead of the basic one
+        payload.extend([0; 4]);
+        let response = self.send(STAT, &payload).await?;
+
+        parse_full_stat(&response)
+    }
+}
+
+fn parse_full_stat(response: &[u8]) -> CrateResult<FullStat> {
+    let mut reader = Reader { data: response };
+    reader.skip(FULL_STAT_PADDING)?;
+
+    let mut values = HashMap::new();
+    loop {
+        let key = reader.string()?;
+        if key.is_empty() {
+            break;
+        }
+        values.insert(key, reader.string()?);
+    }
+
+    reader.skip(PLAYERS_PADDING)?;
+    let mut players = Vec::new();
+    loop {
+        let player = reader.string()?;
+        if player.is_empty() {
+            break;
+        }
+        players.push(player);
+    }
+
+    let mut value = |key: &str| values.remove(key).unwrap_or_default();
+    let (server_mod, plugins) = parse_plugins(&value("plugins"));
+    Ok(FullStat {
+        motd: value("hostname"),
+        game_type: value("gametype"),
+        game_id: value("game_id"),
+        version: value("version"),
+        server_mod,
+        plugins,
+        map: value("map"),
+        online_players: parse_number("numplayers", &value("numplayers"))?,
+        max_players: parse_number("maxplayers", &value("maxplayers"))?,
+        host_port: parse_number("hostport", &value("hostport"))?,
+        host_ip: value("hostip"),
+        players,
+    })
+}
+
+
+#[cfg(test)]
+mod query_test {
+    use super::*;
+
+    const FULL_STAT: &[u8] = b"splitnum\x00\x80\x00\
+        hostname\x00A Minecraft Server\x00gametype\x00SMP\x00game_id\x00MINECRAFT\x00\
+        version\x001.21.1\x00plugins\x00Paper on 1.21.1: LuckPerms 5.4; Essentials 2.20\x00\
+        map\x00world\x00numplayers\x002\x00maxplayers\x0020\x00hostport\x0025565\x00\
+        hostip\x00127.0.0.1\x00\x00\
+        \x01player_\x00\x00Steve\x00Alex\x00\x00";
+
+    /// Answers one handshake and one stat request like a server with query enabled
+    async fn fake_server() -> String {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            let mut buffer = [0; 64];
+            for _ in 0..2 {
+                let (len, peer) = socket.recv_from(&mut buffer).await.unwrap();
+                let request = &buffer[..len];
+                assert_eq!(request[..2], MAGIC);
+
+                let mut response = vec![request[2]];
+                response.extend(&request[3..7]);
+                match request[2] {
+                    HANDSHAKE => response.extend(b"9513307\x00"),
+                    _ => {
+                        assert_eq!(request[7..11], 9513307i32.to_be_bytes());
+                        assert_eq!(len, 15, "full stat request");
+                        response.extend(FULL_STAT);
+                    },
+                }
+                socket.send_to(&response, peer).await.unwrap();
+            }
+        });
+
+        addr
+    }
+
+    #[tokio::test]
+    async fn full_stat_with_challenge_token() {
+        let client = QueryClient::connect(&fake_server().await).await.unwrap();
+        let stat = client.full_stat().await.unwrap();
+
+        assert_eq!(stat.motd, "A Minecraft Server");
+        assert_eq!(stat.version, "1.21.1");
+        assert_eq!(stat.server_mod.as_deref(), Some("Paper on 1.21.1"));
+        assert_eq!(stat.plugins, ["LuckPerms 5.4", "Essentials 2.20"]);
+        assert_eq!((stat.online_players, stat.max_players, stat.host_port), (2, 20, 25565));
+        assert_eq!(stat.players, ["Steve", "Alex"]);
+    }
+
+    #[test]
+    fn parse_basic_stat_fields() {
+        let response = b"A Minecraft Server\x00SMP\x00world\x002\x0020\x00\xDD\x63127.0.0.1\x00";
+        let mut reader = Reader { data: response };
+
+        assert_eq!(reader.string().unwrap(), "A Minecraft Server");
+        reader.skip("SMP\0world\0".len()).unwrap();
+        assert_eq!(reader.string().unwrap(), "2");
+        assert_eq!(reader.string().unwrap(), "20");
+        assert_eq!(reader.u16_le().unwrap(), 25565);
+        assert_eq!(reader.string().unwrap(), "127.0.0.1");
+        assert!(reader.string().is_err());
+    }
+}
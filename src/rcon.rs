@@ -1,17 +1,27 @@
-use tokio::net::{TcpStream, ToSocketAddrs};
-use tokio::sync::Mutex;
-use std::io::prelude::{Read as IORead, Write as IOWrite};
-use std::sync::Arc;
+use serde::Serialize;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::{oneshot, watch, Mutex, MutexGuard, Notify};
+use tokio::task::JoinHandle;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::time::Instant;
 use std::{
-    sync::atomic::{AtomicUsize, Ordering},
+    sync::atomic::{AtomicI32, AtomicU64, Ordering},
 };
 
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 use crate::error::{CrateResult, Error};
 
 
-static PACKET_ID_COUNTER: AtomicUsize = AtomicUsize::new(0);
+/// Smallest valid packet: ID + Type + two null bytes
+const MIN_PACKET_SIZE: i32 = 10;
+/// Minecraft splits responses in fragments of up to 4096 bytes of body
+const MAX_PACKET_SIZE: i32 = 4096 + MIN_PACKET_SIZE;
+/// Request id answered by the server when the password is wrong
+const AUTH_FAILED_ID: i32 = -1;
 
 fn read_i32(data: &[u8]) -> Option<i32> {
     if data.len() != 4 {
@@ -19,15 +29,15 @@ fn read_i32(data: &[u8]) -> Option<i32> {
     }
     let mut arr = [0; 4];
     arr.copy_from_slice(&data[..4]);
-    return Some(i32::from_le_bytes(arr));
+    Some(i32::from_le_bytes(arr))
 }
 
 
 /// Id, Type
-/// 3 	SERVERDATA_AUTH
-/// 2 	SERVERDATA_AUTH_RESPONSE
-/// 2 	SERVERDATA_EXECCOMMAND
-/// 0 	SERVERDATA_RESPONSE_VALUE
+/// 3   SERVERDATA_AUTH
+/// 2   SERVERDATA_AUTH_RESPONSE
+/// 2   SERVERDATA_EXECCOMMAND
+/// 0   SERVERDATA_RESPONSE_VALUE
 #[derive(Debug)]
 pub enum RCONPacketKind {
     Auth,
@@ -36,10 +46,10 @@ pub enum RCONPacketKind {
     ResponseValue,
 }
 
-impl Into<i32> for RCONPacketKind {
-    fn into(self) -> i32 {
+impl From<RCONPacketKind> for i32 {
+    fn from(kind: RCONPacketKind) -> i32 {
         use RCONPacketKind::*;
-        match self {
+        match kind {
             Auth => 3,
             AuthResponse => 2,
             ExecCommand => 2,
@@ -48,16 +58,16 @@ impl Into<i32> for RCONPacketKind {
     }
 }
 
-pub struct RCONPacket<'a> {
+pub struct RCONPacket {
     size: i32,
     id: i32,
     //TODO: provide other way to handle equal kinds like SERVERDATA_AUTH_RESPONSE and SERVERDATA_EXECCOMMAND
     // Both have the same ID, we can differ them by tell which one is a response packet or a request packet
     kind: i32,
-    body: &'a [u8]    
+    body: Vec<u8>,
 }
 
-impl<'a> std::fmt::Display for RCONPacket<'a> {
+impl std::fmt::Display for RCONPacket {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, 
             "{{ Size: {}, Id: {}, Kind: {:?}, Body: {:?} }}", 
@@ -65,105 +75,858 @@ impl<'a> std::fmt::Display for RCONPacket<'a> {
     }
 }
 
-impl<'a> RCONPacket<'a> {    
-    pub fn new<S: Into<&'a str>>(kind: RCONPacketKind, body: S) -> Self {        
-        let s = body.into();
+impl RCONPacket {    
+    pub fn new<S: AsRef<str>>(id: i32, kind: RCONPacketKind, body: S) -> Self {        
+        let s = body.as_ref();
         RCONPacket {
-            //TODO: how many messages can be generate in a running? Does it overflow the i32?
-            id: PACKET_ID_COUNTER.fetch_add(1, Ordering::SeqCst) as i32,
-            body: s.as_bytes(),
+            id,
+            body: s.as_bytes().to_vec(),
             // ID: 4 bytes +  Type: 4 bytes + body.len() + \0 + \0
             size: 4 + 4 + s.len() as i32 + 2,            
             kind: kind.into(),
         }
     }
     
-    pub fn auth_packet(pass: &'a str) -> Self {
-        RCONPacket::new(RCONPacketKind::Auth, pass)
+    pub fn auth_packet(id: i32, pass: &str) -> Self {
+        RCONPacket::new(id, RCONPacketKind::Auth, pass)
     }
-
-    pub async fn send(&self, stream: Arc<Mutex<TcpStream>>) -> CrateResult<()> {
-        let mut stream = stream.lock().await;
-        let mut packet: Vec<u8> = Vec::new();        
+    
+    /// Empty packet sent after a command, its reply marks the end of the command response
+    pub fn terminator_packet(id: i32) -> Self {
+        RCONPacket::new(id, RCONPacketKind::ResponseValue, "")
+    }
+    
+    fn encode(&self) -> Vec<u8> {
+        let mut packet: Vec<u8> = Vec::with_capacity(4 + self.size as usize);
         
         packet.extend(&self.size.to_le_bytes());   // Size (4 bytes)
         packet.extend(&self.id.to_le_bytes());     // ID (4 bytes)
         packet.extend(&self.kind.to_le_bytes());   // Type (4 bytes)
-        packet.extend(self.body);                  // Body (14 bytes)
+        packet.extend(&self.body);                 // Body (size - 10 bytes)
         packet.push(0);                            // Null terminator
         packet.push(0);                            // Second null byte
-        
-        for byte in &packet {
-            print!("{:02X} ", byte);
-        }
-        println!();
-        
-        stream.write_all(&packet).await.map_err(Error::connection_error)?;
+        packet
+    }
+
+    pub async fn send<W: AsyncWrite + Unpin>(&self, stream: &mut W) -> CrateResult<()> {
+        stream.write_all(&self.encode()).await.map_err(Error::connection_error)?;
         Ok(())
     }
     
-    pub async fn recv(&self, stream: Arc<Mutex<TcpStream>>) -> CrateResult<Self> {
-        let mut stream = stream.lock().await;   
-        let mut buffer = [0; 4096];
-        let readed_n = stream.read(&mut buffer[..]).await.map_err(Error::connection_error)?;
+    /// Reads exactly one packet, waiting until all the `size` bytes arrive
+    pub async fn recv<R: AsyncRead + Unpin>(stream: &mut R) -> CrateResult<Self> {
+        let mut size_bytes = [0; 4];
+        stream.read_exact(&mut size_bytes).await.map_err(Error::connection_error)?;
         
-        println!("Readed {}", readed_n);
-        for byte in &buffer[..readed_n] {
-            print!("{:02X} ", byte);
+        let size = i32::from_le_bytes(size_bytes);
+        if !(MIN_PACKET_SIZE..=MAX_PACKET_SIZE).contains(&size) {
+            return Err(Error::connection_error(format!("invalid packet size: {size}")));
         }
-        println!();
         
-        let readed = Self {
-            size: read_i32(&buffer[..4]).unwrap(),
-            id: read_i32(&buffer[4..8]).unwrap(),
-            kind: read_i32(&buffer[8..12]).unwrap(),
-            body: &[0; 0],
-        };
+        let mut payload = vec![0; size as usize];
+        stream.read_exact(&mut payload).await.map_err(Error::connection_error)?;
         
-        println!("Readed {readed}");
-        Ok(readed)
+        // drop the two null bytes after the body
+        payload.truncate(size as usize - 2);
+        
+        Ok(Self {
+            size,
+            id: read_i32(&payload[..4]).unwrap(),
+            kind: read_i32(&payload[4..8]).unwrap(),
+            body: payload.split_off(8),
+        })
     }   
     
-    pub async fn send_sync(&self, stream: Arc<Mutex<TcpStream>>) -> CrateResult<Self> {        
-        self.send(Arc::clone(&stream)).await?;
+    /// Sends the packet and waits the next packet of the given kind.
+    ///
+    /// Packets of other kinds are skipped, some servers send an empty
+    /// SERVERDATA_RESPONSE_VALUE before the SERVERDATA_AUTH_RESPONSE.
+    pub async fn send_sync(
+        &self,
+        stream: &mut TcpStream,
+        kind: RCONPacketKind,
+    ) -> CrateResult<Self> {
+        let kind: i32 = kind.into();
+        self.send(stream).await?;
         
-        let resp = self.recv(Arc::clone(&stream)).await?;
-        assert_eq!(resp.id, self.id, "received message with not equal ID: {}", resp);
+        loop {
+            let resp = Self::recv(stream).await?;
+            if resp.kind == kind {
+                return Ok(resp);
+            }
+        }
+    }
+    
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+}
+
+/// Reply of a command executed through RCON
+#[derive(Debug, Serialize)]
+pub struct RconResponse {
+    pub output: String,
+    pub request_id: i32,
+}
+
+/// Delays used by the supervisor to reconnect a dropped connection
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    /// Wait before the second attempt, doubled on every failure
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+}
 
-        Ok(resp)
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
     }
 }
 
-pub struct RconConnection {
-    stream: Arc<Mutex<TcpStream>>,
+impl ReconnectPolicy {
+    fn delay(&self, attempt: u32) -> Duration {
+        self.initial_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay)
+    }
 }
 
-impl RconConnection {    
-    /// Returns a authenticated session
-    pub async fn connect<A: ToSocketAddrs>(addr: A, pass: &str) -> CrateResult<Self> {
-        let mut stream = TcpStream::connect(addr)
+/// Longest waits of a connection, each one fails with [`Error::Timeout`]
+#[derive(Debug, Clone)]
+pub struct Timeouts {
+    /// Opening the TCP socket
+    pub connect: Duration,
+    /// Reply of the server to the password
+    pub auth: Duration,
+    /// Whole response of a command, since the call
+    pub command: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            connect: Duration::from_secs(5),
+            auth: Duration::from_secs(5),
+            command: Duration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ConnectionState {
+    Connected,
+    /// Commands are rejected until the supervisor authenticates a new socket
+    Reconnecting { attempt: u32, last_error: String },
+}
+
+/// Packet ids of one connection, wrapping around on overflow
+struct PacketIds(AtomicI32);
+
+impl PacketIds {
+    fn next(&self) -> i32 {
+        loop {
+            let id = self.0.fetch_add(1, Ordering::Relaxed);
+            // the server answers a rejected password with -1
+            if id != AUTH_FAILED_ID {
+                return id;
+            }
+        }
+    }
+}
+
+/// Command waiting the fragments of its response
+struct Pending {
+    /// Id of the empty packet sent after the command, its reply ends the response
+    terminator_id: i32,
+    body: Vec<u8>,
+    reply: oneshot::Sender<CrateResult<Vec<u8>>>,
+}
+
+/// Forgets the pending command when its caller stops waiting, so its late response is
+/// dropped by the reader
+struct PendingGuard<'a> {
+    shared: &'a Shared,
+    id: i32,
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        self.shared.pending.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Socket lock held while a packet is written, a packet cut in half by a timeout or a
+/// cancelled caller would corrupt every later packet, so the socket is thrown away then
+struct WriteGuard<'a> {
+    shared: &'a Shared,
+    socket: MutexGuard<'a, Option<Socket>>,
+    written: bool,
+}
+
+impl Drop for WriteGuard<'_> {
+    fn drop(&mut self) {
+        if !self.written {
+            let err = Error::connection_error("request cancelled while being written");
+            self.shared.mark_dropped(&mut self.socket, &err);
+        }
+    }
+}
+
+/// Authenticated socket, its read half belongs to the reader task
+struct Socket {
+    writer: OwnedWriteHalf,
+    reader: JoinHandle<()>,
+    /// Tells the sockets apart, so a late reader can't drop the socket replacing its own
+    generation: u64,
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+struct Shared {
+    addr: String,
+    password: String,
+    policy: ReconnectPolicy,
+    timeouts: Timeouts,
+    ids: PacketIds,
+    generations: AtomicU64,
+    /// `None` while the supervisor is reconnecting, the lock is only held to write requests
+    socket: Mutex<Option<Socket>>,
+    /// Commands sent on the current socket, by packet id
+    pending: std::sync::Mutex<HashMap<i32, Pending>>,
+    /// Packets written on the current socket and not answered yet, the next packet is only
+    /// written once the server answered every previous one
+    unread: watch::Sender<HashSet<i32>>,
+    state: watch::Sender<ConnectionState>,
+    dropped: Arc<Notify>,
+}
+
+impl Drop for Shared {
+    /// Wakes the supervisor so it notices the connection is gone
+    fn drop(&mut self) {
+        self.dropped.notify_one();
+    }
+}
+
+impl Shared {
+    /// Throws away the socket, fails the commands waiting on it and wakes the supervisor
+    fn mark_dropped(&self, socket: &mut Option<Socket>, err: &Error) {
+        *socket = None;
+        self.unread.send_modify(HashSet::clear);
+        for (_, command) in self.pending.lock().unwrap().drain() {
+            let _ = command.reply.send(Err(Error::rcon_unavailable(err)));
+        }
+        self.state.send_replace(ConnectionState::Reconnecting {
+            attempt: 0,
+            last_error: err.to_string(),
+        });
+        self.dropped.notify_one();
+    }
+    
+    /// Connects, authenticates and starts the reader of a new socket
+    async fn open(self: &Arc<Self>) -> CrateResult<()> {
+        let connect = self.timeouts.connect;
+        let mut stream = tokio::time::timeout(connect, TcpStream::connect(&self.addr))
             .await
+            .map_err(|_| Error::timeout(format!("connecting to {} after {connect:?}", self.addr)))?
             .map_err(Error::connection_error)?;
-                
-        let mut conn = Self { stream: Arc::new(Mutex::new(stream)) };
+        let auth = self.timeouts.auth;
+        tokio::time::timeout(auth, self.rcon_auth(&mut stream))
+            .await
+            .map_err(|_| Error::timeout(format!("no auth response after {auth:?}")))??;
+        
+        let (read_half, writer) = stream.into_split();
+        let generation = self.generations.fetch_add(1, Ordering::Relaxed);
+        let reader = Self::read_responses(Arc::downgrade(self), read_half, generation);
+        let reader = tokio::spawn(reader);
         
-        conn.rcon_auth(pass).await?;
+        self.unread.send_replace(HashSet::new());
+        *self.socket.lock().await = Some(Socket { writer, reader, generation });
+        self.state.send_replace(ConnectionState::Connected);
+        Ok(())
+    }
 
+    /// Fails with [`Error::RconAuthFailed`] when the server answers with the id -1
+    async fn rcon_auth(&self, stream: &mut TcpStream) -> CrateResult<()> {
+        let request = RCONPacket::auth_packet(self.ids.next(), &self.password);
+        let resp = request
+            .send_sync(stream, RCONPacketKind::AuthResponse)
+            .await?;
+        
+        match resp.id {
+            AUTH_FAILED_ID => Err(Error::rcon_auth_failed("server rejected the password")),
+            id if id == request.id => Ok(()),
+            id => Err(Error::connection_error(
+                format!("auth response with id {id}, expected {}", request.id))),
+        }
+    }
+    
+    /// Hands every packet to the command with the same id, until the socket fails
+    async fn read_responses(shared: Weak<Self>, mut reader: OwnedReadHalf, generation: u64) {
+        let err = loop {
+            match RCONPacket::recv(&mut reader).await {
+                Ok(packet) => match shared.upgrade() {
+                    Some(shared) => shared.route(packet),
+                    None => return,
+                },
+                Err(err) => break err,
+            }
+        };
+        
+        let Some(shared) = shared.upgrade() else {
+            return;
+        };
+        let mut socket = shared.socket.lock().await;
+        if socket.as_ref().is_some_and(|socket| socket.generation == generation) {
+            shared.mark_dropped(&mut socket, &err);
+        }
+    }
+    
+    fn route(&self, packet: RCONPacket) {
+        self.unread.send_if_modified(|ids| ids.remove(&packet.id));
+        
+        let mut pending = self.pending.lock().unwrap();
+        if let Some(command) = pending.get_mut(&packet.id) {
+            command.body.extend(packet.body);
+            return;
+        }
+        
+        let finished = pending
+            .iter()
+            .find(|(_, command)| command.terminator_id == packet.id)
+            .map(|(id, _)| *id);
+        if let Some(command) = finished.and_then(|id| pending.remove(&id)) {
+            let _ = command.reply.send(Ok(command.body));
+        }
+        // anything else answers a command nobody waits anymore
+    }
+}
+
+/// Authenticated RCON session that reconnects by itself when the socket drops.
+///
+/// Commands can run concurrently, a background task reads the socket and routes each
+/// response to its caller by packet id.
+pub struct RconConnection {
+    shared: Arc<Shared>,
+}
+
+impl RconConnection {    
+    /// Returns a authenticated session
+    pub async fn connect<A: Into<String>>(addr: A, pass: &str) -> CrateResult<Self> {
+        Self::connect_with_policy(addr, pass, ReconnectPolicy::default(), Timeouts::default()).await
+    }
+    
+    pub async fn connect_with_policy<A: Into<String>>(
+        addr: A,
+        pass: &str,
+        policy: ReconnectPolicy,
+        timeouts: Timeouts,
+    ) -> CrateResult<Self> {
+        let conn = Self::new(addr.into(), pass, policy, timeouts);
+        conn.shared.open().await?;
+        
         Ok(conn)
     }
+    
+    /// Returns without waiting the server, the supervisor keeps trying to connect in background
+    pub fn spawn<A: Into<String>>(
+        addr: A,
+        pass: &str,
+        policy: ReconnectPolicy,
+        timeouts: Timeouts,
+    ) -> Self {
+        let conn = Self::new(addr.into(), pass, policy, timeouts);
+        conn.shared.dropped.notify_one();
+        conn
+    }
+    
+    fn new(addr: String, pass: &str, policy: ReconnectPolicy, timeouts: Timeouts) -> Self {
+        let shared = Arc::new(Shared {
+            addr,
+            password: pass.to_string(),
+            policy,
+            timeouts,
+            ids: PacketIds(AtomicI32::new(0)),
+            generations: AtomicU64::new(0),
+            socket: Mutex::new(None),
+            pending: std::sync::Mutex::new(HashMap::new()),
+            unread: watch::Sender::new(HashSet::new()),
+            state: watch::Sender::new(ConnectionState::Reconnecting {
+                attempt: 0,
+                last_error: "not connected yet".to_string(),
+            }),
+            dropped: Arc::new(Notify::new()),
+        });
+        tokio::spawn(Self::supervise(Arc::downgrade(&shared), Arc::clone(&shared.dropped)));
 
-    async fn rcon_auth(&self, pass: &str) -> CrateResult<()> {
-        RCONPacket::auth_packet(pass)
-            .send_sync(Arc::clone(&self.stream))
-            .await?;
-        Ok(())
+        Self { shared }
     }
     
-    pub async fn exec_command(&self, cmd: String) -> CrateResult<()> {
-        let resp = RCONPacket::new(RCONPacketKind::ExecCommand, cmd.as_str())
-            .send_sync(Arc::clone(&self.stream))
-            .await?;
-        println!("{resp}");
+    /// Background task reconnecting with exponential backoff, it only keeps a weak reference
+    /// so it stops once the connection is dropped.
+    async fn supervise(shared: Weak<Shared>, dropped: Arc<Notify>) {
+        loop {
+            dropped.notified().await;
+            
+            let mut attempt = 0;
+            loop {
+                let Some(shared) = shared.upgrade() else {
+                    return;
+                };
+                if shared.socket.lock().await.is_some() {
+                    break;
+                }
+                
+                match shared.open().await {
+                    Ok(()) => break,
+                    Err(err) => {
+                        attempt += 1;
+                        shared.state.send_replace(ConnectionState::Reconnecting {
+                            attempt,
+                            last_error: err.to_string(),
+                        });
+                        let delay = shared.policy.delay(attempt - 1);
+                        drop(shared);
+                        tokio::time::sleep(delay).await;
+                    },
+                }
+            }
+        }
+    }
+    
+    /// Current state of the underlying socket
+    pub fn state(&self) -> ConnectionState {
+        self.shared.state.borrow().clone()
+    }
+    
+    /// Receives every change of [`ConnectionState`]
+    pub fn subscribe(&self) -> watch::Receiver<ConnectionState> {
+        self.shared.state.subscribe()
+    }
+    
+    /// Fails with [`Error::RconUnavailable`] while the connection is being reestablished, and
+    /// with [`Error::Timeout`] when the response takes longer than [`Timeouts::command`]
+    pub async fn exec_command(&self, cmd: String) -> CrateResult<RconResponse> {
+        let request = RCONPacket::new(self.shared.ids.next(), RCONPacketKind::ExecCommand, &cmd);
+        // the server answers packets in order, so the reply of the terminator can only
+        // arrive once the whole response was sent
+        let terminator = RCONPacket::terminator_packet(self.shared.ids.next());
+        
+        let body = self.send(request.id, Some(&request), &terminator).await?;
+        Ok(RconResponse {
+            output: String::from_utf8_lossy(&body).into_owned(),
+            request_id: request.id(),
+        })
+    }
+    
+    /// Round trip of a lone terminator packet, the server answers it without running
+    /// any command
+    pub async fn ping(&self) -> CrateResult<()> {
+        // nothing is sent with this id, it only keys the pending probe
+        let id = self.shared.ids.next();
+        let probe = RCONPacket::terminator_packet(self.shared.ids.next());
+        self.send(id, None, &probe).await?;
         Ok(())
     }
-}
\ No newline at end of file
+    
+    /// Throws away the socket as if it failed, the supervisor opens a new one
+    pub async fn reconnect(&self, err: &Error) {
+        let mut socket = self.shared.socket.lock().await;
+        if socket.is_some() {
+            self.shared.mark_dropped(&mut socket, err);
+        }
+    }
+    
+    /// Writes `request` and `terminator`, and waits the body collected under `id` until
+    /// the reply of `terminator`
+    async fn send(
+        &self,
+        id: i32,
+        request: Option<&RCONPacket>,
+        terminator: &RCONPacket,
+    ) -> CrateResult<Vec<u8>> {
+        let timeout = self.shared.timeouts.command;
+        let deadline = Instant::now() + timeout;
+        let timed_out = || Error::timeout(format!("no response after {timeout:?}"));
+        
+        let (reply, response) = oneshot::channel();
+        let _pending = PendingGuard { shared: &self.shared, id };
+        // registered before writing, the reader may get the reply before the write returns
+        let pending = Pending { terminator_id: terminator.id, body: Vec::new(), reply };
+        self.shared.pending.lock().unwrap().insert(id, pending);
+        
+        let written = async {
+            if let Some(request) = request {
+                self.write(request).await?;
+            }
+            self.write(terminator).await
+        };
+        tokio::time::timeout_at(deadline, written).await.map_err(|_| timed_out())??;
+        
+        tokio::time::timeout_at(deadline, response)
+            .await
+            .map_err(|_| timed_out())?
+            .map_err(|_| Error::connection_error("connection closed before the response"))?
+    }
+    
+    /// Writes one packet once the server answered every packet written before it.
+    ///
+    /// Vanilla reads a packet with a single `read` and closes the socket when it gets more
+    /// bytes than the packet, so packets must never pile up in the stream.
+    async fn write(&self, packet: &RCONPacket) -> CrateResult<()> {
+        let mut unread = self.shared.unread.subscribe();
+        loop {
+            unread.wait_for(HashSet::is_empty).await.map_err(Error::connection_error)?;
+            
+            let socket = self.shared.socket.lock().await;
+            let mut guard = WriteGuard { shared: &self.shared, socket, written: false };
+            let Some(socket) = guard.socket.as_mut() else {
+                guard.written = true;
+                return Err(Error::rcon_unavailable(format!("{:?}", self.state())));
+            };
+            // another caller wrote between the wait and the lock
+            if !self.shared.unread.borrow().is_empty() {
+                guard.written = true;
+                continue;
+            }
+            
+            self.shared.unread.send_modify(|ids| {
+                ids.insert(packet.id);
+            });
+            let written = socket.writer.write_all(&packet.encode()).await;
+            guard.written = true;
+            if let Err(err) = written {
+                let err = Error::connection_error(err);
+                self.shared.mark_dropped(&mut guard.socket, &err);
+                return Err(err);
+            }
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod fake_server {
+    //! RCON server answering like Minecraft, shared by the tests of the connection and
+    //! of the pool.
+
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use tokio::{
+        io::AsyncWriteExt,
+        net::{TcpListener, TcpStream},
+        sync::Semaphore,
+    };
+
+    use super::*;
+
+    pub(crate) const PASSWORD: &str = "secret";
+
+    /// Reads the next packet like the `RconClient` of vanilla: a single read of up to 1460
+    /// bytes, and the socket is closed unless they hold exactly one packet
+    pub(crate) async fn read_packet(socket: &mut TcpStream) -> Option<RCONPacket> {
+        let mut buf = [0; 1460];
+        let len = socket.read(&mut buf).await.ok()?;
+        if len < MIN_PACKET_SIZE as usize + 4 {
+            return None;
+        }
+        let size = read_i32(&buf[..4])?;
+        if size as usize != len - 4 {
+            return None;
+        }
+        RCONPacket::recv(&mut &buf[..len]).await.ok()
+    }
+
+    /// Answers, one command at a time per socket and one packet per read:
+    ///
+    /// - `/stop` by closing the socket, like a server restart
+    /// - `/echo <text>` with the text
+    /// - `/socket` with the number of the socket, counting from 1
+    /// - `/wait` once the test calls [`FakeServer::release`]
+    /// - any other command with `reply` split in fragments of `fragment` bytes
+    /// - the terminator packets the way Minecraft answers unknown packet types
+    pub(crate) struct FakeServer {
+        pub addr: String,
+        sockets: Arc<AtomicUsize>,
+        waiting: Arc<AtomicUsize>,
+        gate: Arc<Semaphore>,
+    }
+
+    fn response(id: i32, body: &str) -> Vec<u8> {
+        RCONPacket::new(id, RCONPacketKind::ResponseValue, body).encode()
+    }
+
+    impl FakeServer {
+        pub(crate) async fn start(reply: &'static str, fragment: usize) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let server = Self {
+                addr: listener.local_addr().unwrap().to_string(),
+                sockets: Arc::new(AtomicUsize::new(0)),
+                waiting: Arc::new(AtomicUsize::new(0)),
+                gate: Arc::new(Semaphore::new(0)),
+            };
+
+            let (sockets, waiting, gate) =
+                (server.sockets.clone(), server.waiting.clone(), server.gate.clone());
+            tokio::spawn(async move {
+                while let Ok((mut socket, _)) = listener.accept().await {
+                    let number = sockets.fetch_add(1, Ordering::SeqCst) + 1;
+                    let (waiting, gate) = (waiting.clone(), gate.clone());
+                    tokio::spawn(async move {
+                        while let Some(packet) = read_packet(&mut socket).await {
+                            let mut out = Vec::new();
+                            match packet.kind {
+                                3 if packet.body == PASSWORD.as_bytes() => out.extend(
+                                    RCONPacket::new(packet.id, RCONPacketKind::AuthResponse, "")
+                                        .encode(),
+                                ),
+                                3 => out.extend(
+                                    RCONPacket::new(AUTH_FAILED_ID, RCONPacketKind::AuthResponse, "")
+                                        .encode(),
+                                ),
+                                2 if packet.body == b"/stop" => return,
+                                2 if packet.body.starts_with(b"/echo ") => {
+                                    let text = std::str::from_utf8(&packet.body[6..]).unwrap();
+                                    out.extend(response(packet.id, text))
+                                },
+                                2 if packet.body == b"/socket" => {
+                                    out.extend(response(packet.id, &number.to_string()))
+                                },
+                                2 if packet.body == b"/wait" => {
+                                    waiting.fetch_add(1, Ordering::SeqCst);
+                                    gate.acquire().await.unwrap().forget();
+                                    waiting.fetch_sub(1, Ordering::SeqCst);
+                                    out.extend(response(packet.id, "released"))
+                                },
+                                2 => {
+                                    for chunk in reply.as_bytes().chunks(fragment) {
+                                        let chunk = std::str::from_utf8(chunk).unwrap();
+                                        out.extend(response(packet.id, chunk));
+                                    }
+                                },
+                                kind => {
+                                    out.extend(response(packet.id, &format!("Unknown request {kind:x}")))
+                                },
+                            }
+                            if socket.write_all(&out).await.is_err() {
+                                return;
+                            }
+                        }
+                    });
+                }
+            });
+
+            server
+        }
+
+        /// Sockets accepted so far
+        pub(crate) fn sockets(&self) -> usize {
+            self.sockets.load(Ordering::SeqCst)
+        }
+
+        /// `/wait` commands not released yet
+        pub(crate) fn waiting(&self) -> usize {
+            self.waiting.load(Ordering::SeqCst)
+        }
+
+        /// Answers the next `commands` `/wait` commands
+        pub(crate) fn release(&self, commands: usize) {
+            self.gate.add_permits(commands);
+        }
+    }
+
+    /// Polls `done` until it holds, the background tasks run meanwhile
+    pub(crate) async fn eventually(done: impl Fn() -> bool) {
+        let poll = async {
+            while !done() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(10), poll).await.expect("condition never met");
+    }
+}
+
+#[cfg(test)]
+mod rcon_test {
+    use tokio::net::TcpListener;
+
+    use super::{fake_server::*, *};
+    
+    #[tokio::test]
+    async fn exec_command_returns_output() {
+        let server = FakeServer::start("There are 0 of a max of 20 players online: ", 4096).await;
+        let conn = RconConnection::connect(server.addr, PASSWORD).await.unwrap();
+        
+        let resp = conn.exec_command("/list".to_string()).await.unwrap();
+        
+        assert_eq!(resp.output, "There are 0 of a max of 20 players online: ");
+    }
+    
+    #[tokio::test]
+    async fn exec_command_reassembles_fragments() {
+        let reply: &'static str = "a".repeat(10_000).leak();
+        let server = FakeServer::start(reply, 4096).await;
+        let conn = RconConnection::connect(server.addr, PASSWORD).await.unwrap();
+        
+        let first = conn.exec_command("/help".to_string()).await.unwrap();
+        let second = conn.exec_command("/help".to_string()).await.unwrap();
+        
+        assert_eq!(first.output.len(), 10_000);
+        assert_eq!(second.output.len(), 10_000);
+    }
+    
+    #[tokio::test]
+    async fn recv_waits_for_split_reads() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let packet = RCONPacket::new(7, RCONPacketKind::ResponseValue, "hello world").encode();
+        
+        tokio::spawn(async move {
+            for chunk in packet.chunks(3) {
+                server.write_all(chunk).await.unwrap();
+                tokio::task::yield_now().await;
+            }
+        });
+        
+        let resp = RCONPacket::recv(&mut client).await.unwrap();
+        assert_eq!(resp.id(), 7);
+        assert_eq!(resp.body, b"hello world");
+    }
+    
+    #[tokio::test]
+    async fn connect_with_wrong_password() {
+        let server = FakeServer::start("", 4096).await;
+        
+        let result = RconConnection::connect(server.addr, "wrong").await;
+        
+        assert!(matches!(result, Err(Error::RconAuthFailed { .. })));
+    }
+    
+    #[tokio::test]
+    async fn reconnects_after_socket_drop() {
+        let server = FakeServer::start("ok", 4096).await;
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(50),
+        };
+        let conn =
+            RconConnection::connect_with_policy(server.addr, PASSWORD, policy, Timeouts::default())
+                .await
+                .unwrap();
+        let mut state = conn.subscribe();
+        
+        assert!(conn.exec_command("/stop".to_string()).await.is_err());
+        assert!(matches!(conn.state(), ConnectionState::Reconnecting { .. }));
+        
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            state.wait_for(|s| *s == ConnectionState::Connected),
+        ).await.unwrap().unwrap();
+        
+        assert_eq!(conn.exec_command("/list".to_string()).await.unwrap().output, "ok");
+    }
+    
+    #[tokio::test]
+    async fn concurrent_commands_get_their_own_response() {
+        let server = FakeServer::start("", 4096).await;
+        let conn = Arc::new(RconConnection::connect(server.addr, PASSWORD).await.unwrap());
+        
+        let calls = (0..50).map(|i| {
+            let conn = Arc::clone(&conn);
+            tokio::spawn(async move { (i, conn.exec_command(format!("/echo {i}")).await) })
+        });
+        for call in calls {
+            let (i, resp) = call.await.unwrap();
+            assert_eq!(resp.unwrap().output, i.to_string());
+        }
+        assert!(conn.shared.pending.lock().unwrap().is_empty());
+    }
+    
+    #[tokio::test]
+    async fn server_drops_packets_sent_together() {
+        let server = FakeServer::start("ok", 4096).await;
+        let mut stream = TcpStream::connect(&server.addr).await.unwrap();
+        
+        let mut packets = RCONPacket::auth_packet(1, PASSWORD).encode();
+        packets.extend(RCONPacket::new(2, RCONPacketKind::ExecCommand, "/list").encode());
+        stream.write_all(&packets).await.unwrap();
+        
+        assert!(RCONPacket::recv(&mut stream).await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn packets_are_written_one_at_a_time() {
+        let server = FakeServer::start("ok", 4096).await;
+        let conn = Arc::new(RconConnection::connect(server.addr.clone(), PASSWORD).await.unwrap());
+        
+        let calls: Vec<_> = (0..20)
+            .map(|_| {
+                let conn = Arc::clone(&conn);
+                tokio::spawn(async move { conn.exec_command("/list".to_string()).await })
+            })
+            .collect();
+        for call in calls {
+            assert_eq!(call.await.unwrap().unwrap().output, "ok");
+        }
+        assert!(conn.ping().await.is_ok());
+        
+        // a command and its terminator read together would have closed the socket
+        assert_eq!(server.sockets(), 1);
+        assert_eq!(conn.state(), ConnectionState::Connected);
+    }
+    
+    #[tokio::test]
+    async fn late_response_of_timed_out_command_is_dropped() {
+        let server = FakeServer::start("", 4096).await;
+        let timeouts = Timeouts { command: Duration::from_millis(50), ..Timeouts::default() };
+        let conn = RconConnection::connect_with_policy(
+            server.addr.clone(), PASSWORD, ReconnectPolicy::default(), timeouts,
+        ).await.unwrap();
+        
+        let result = conn.exec_command("/wait".to_string()).await;
+        assert!(matches!(result, Err(Error::Timeout { .. })));
+        assert!(conn.shared.pending.lock().unwrap().is_empty());
+        
+        // answered right after the late "released"
+        server.release(1);
+        assert_eq!(conn.exec_command("/echo next".to_string()).await.unwrap().output, "next");
+        assert_eq!(conn.state(), ConnectionState::Connected);
+    }
+    
+    #[tokio::test]
+    async fn connect_times_out_without_auth_response() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (_socket, _) = listener.accept().await.unwrap();
+            std::future::pending::<()>().await;
+        });
+        
+        let timeouts = Timeouts { auth: Duration::from_millis(50), ..Timeouts::default() };
+        let result =
+            RconConnection::connect_with_policy(addr, "secret", ReconnectPolicy::default(), timeouts)
+                .await;
+        
+        assert!(matches!(result, Err(Error::Timeout { .. })));
+    }
+    
+    #[test]
+    fn packet_ids_wrap_around_without_minus_one() {
+        let ids = PacketIds(AtomicI32::new(i32::MAX));
+        assert_eq!(ids.next(), i32::MAX);
+        assert_eq!(ids.next(), i32::MIN);
+        
+        let ids = PacketIds(AtomicI32::new(-2));
+        assert_eq!(ids.next(), -2);
+        assert_eq!(ids.next(), 0);
+    }
+}
@@ -0,0 +1,186 @@
+use std::{collections::HashMap, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use tokio::sync::RwLock;
+
+use crate::{
+    error::{CrateResult, Error},
+    ping::DEFAULT_GAME_PORT,
+    pool::{PoolConfig, RconPool},
+    query::DEFAULT_QUERY_PORT,
+    rcon::{ConnectionState, ReconnectPolicy},
+};
+
+/// Name of the server configured through the `--host/--port/--password` flags
+pub const DEFAULT_SERVER: &str = "default";
+
+/// Connection settings of a named Minecraft server
+#[derive(Clone, Deserialize, sqlx::FromRow)]
+pub struct RconTarget {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub password: String,
+    /// Port of the Query protocol, [`DEFAULT_QUERY_PORT`] when missing
+    #[serde(default)]
+    pub query_port: Option<u16>,
+    /// Port of the players, answering the Server List Ping, [`DEFAULT_GAME_PORT`] when missing
+    #[serde(default)]
+    pub game_port: Option<u16>,
+}
+
+impl RconTarget {
+    fn addr(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+
+    pub(crate) fn query_addr(&self) -> String {
+        format!("{}:{}", self.host, self.query_port.unwrap_or(DEFAULT_QUERY_PORT))
+    }
+
+    pub(crate) fn game_port(&self) -> u16 {
+        self.game_port.unwrap_or(DEFAULT_GAME_PORT)
+    }
+}
+
+/// Server as shown to HTTP clients, without the RCON password
+#[derive(Serialize)]
+pub(crate) struct ServerInfo {
+    name: String,
+    host: String,
+    port: u16,
+    connection: ConnectionState,
+    /// RCON sockets open in the pool
+    connections: usize,
+}
+
+/// Named RCON connection pools, the targets are stored in the `rcon_servers` table
+pub struct ServerRegistry {
+    pool: Arc<SqlitePool>,
+    rcon: PoolConfig,
+    servers: RwLock<HashMap<String, (RconTarget, Arc<RconPool>)>>,
+}
+
+impl ServerRegistry {
+    pub fn new(pool: Arc<SqlitePool>, rcon: PoolConfig) -> Self {
+        Self {
+            pool,
+            rcon,
+            servers: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Connects every stored server that isn't connected yet
+    pub async fn load(&self) -> CrateResult<()> {
+        let targets: Vec<RconTarget> =
+            sqlx::query_as("
+                SELECT name, host, port, password, query_port, game_port FROM rcon_servers
+                ")
+                .fetch_all(Arc::as_ref(&self.pool))
+                .await
+                .map_err(Error::server_error)?;
+
+        for target in targets {
+            if self.servers.read().await.contains_key(&target.name) {
+                continue;
+            }
+            let conn = self.connect(&target).await?;
+            self.insert(target, conn).await;
+        }
+
+        Ok(())
+    }
+
+    /// Connects to the target and stores it, replacing the one with the same name. A
+    /// rejected password is never stored.
+    pub async fn add_server(&self, target: RconTarget) -> CrateResult<()> {
+        let conn = self.connect(&target).await?;
+
+        sqlx::query("
+            INSERT INTO rcon_servers(name, host, port, password, query_port, game_port)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT(name) DO UPDATE
+            SET host = $2, port = $3, password = $4, query_port = $5, game_port = $6;
+            ")
+            .bind(&target.name)
+            .bind(&target.host)
+            .bind(target.port)
+            .bind(&target.password)
+            .bind(target.query_port)
+            .bind(target.game_port)
+            .execute(Arc::as_ref(&self.pool))
+            .await
+            .map_err(Error::server_error)?;
+
+        self.insert(target, conn).await;
+        Ok(())
+    }
+
+    /// A wrong password is reported right away, a server that is down keeps being retried
+    /// in background.
+    async fn connect(&self, target: &RconTarget) -> CrateResult<RconPool> {
+        let policy = ReconnectPolicy::default();
+        let (addr, password) = (target.addr(), target.password.as_str());
+        let conn = match RconPool::connect(&addr, password, policy.clone(), self.rcon.clone()).await {
+            Ok(conn) => conn,
+            Err(err @ Error::RconAuthFailed { .. }) => return Err(err),
+            Err(err) => {
+                log::warn!("{} is not reachable, retrying in background: {}", target.name, err);
+                RconPool::spawn(addr, password, policy, self.rcon.clone())
+            },
+        };
+        Ok(conn)
+    }
+
+    async fn insert(&self, target: RconTarget, conn: RconPool) {
+        let mut state = conn.subscribe();
+        let name = target.name.clone();
+        tokio::spawn(async move {
+            while state.changed().await.is_ok() {
+                log::info!("{} RCON connection state: {:?}", name, *state.borrow_and_update());
+            }
+        });
+
+        self.servers
+            .write()
+            .await
+            .insert(target.name.clone(), (target, Arc::new(conn)));
+    }
+
+    pub(crate) async fn get(&self, name: &str) -> CrateResult<Arc<RconPool>> {
+        self.servers
+            .read()
+            .await
+            .get(name)
+            .map(|(_, conn)| Arc::clone(conn))
+            .ok_or_else(|| Error::server_not_found(name))
+    }
+
+    /// Connection settings of the server `name`
+    pub(crate) async fn target(&self, name: &str) -> CrateResult<RconTarget> {
+        self.servers
+            .read()
+            .await
+            .get(name)
+            .map(|(target, _)| target.clone())
+            .ok_or_else(|| Error::server_not_found(name))
+    }
+
+    pub(crate) async fn list(&self) -> Vec<ServerInfo> {
+        let mut servers: Vec<ServerInfo> = self.servers
+            .read()
+            .await
+            .values()
+            .map(|(target, conn)| ServerInfo {
+                name: target.name.clone(),
+                host: target.host.clone(),
+                port: target.port,
+                connection: conn.state(),
+                connections: conn.open_connections(),
+            })
+            .collect();
+        servers.sort_by(|a, b| a.name.cmp(&b.name));
+        servers
+    }
+}
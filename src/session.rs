@@ -0,0 +1,289 @@
+use std::{collections::HashMap, sync::Arc};
+
+use actix_session::storage::{
+    generate_session_key, LoadError, SaveError, SessionKey, SessionStore, UpdateError,
+};
+use actix_web::{
+    cookie::{time::Duration, CookieJar, Key},
+    HttpRequest,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256, Sha512};
+use sqlx::SqlitePool;
+
+use crate::{
+    clock::unix_now,
+    error::{CrateResult, Error},
+    token::to_hex,
+};
+
+/// Name of the cookie holding the encrypted session key
+pub(crate) const SESSION_COOKIE: &str = "id";
+
+/// Prefix of the session entries of actix-identity
+const IDENTITY_PREFIX: &str = "actix_identity.";
+/// Session entry where actix-identity keeps the logged user
+const IDENTITY_KEY: &str = "actix_identity.user_id";
+
+/// Cookie signing key derived from the secret key, so sessions survive restarts
+pub(crate) fn session_key(secret_key: &str) -> Key {
+    let digest = Sha512::new()
+        .chain_update(b"mc-phone session key:")
+        .chain_update(secret_key.as_bytes())
+        .finalize();
+    Key::from(&digest)
+}
+
+fn session_hash(session_key: &SessionKey) -> String {
+    to_hex(&Sha256::digest(session_key.as_ref().as_bytes()))
+}
+
+/// actix-identity stores the user id as a JSON string
+fn session_nick(state: &HashMap<String, String>) -> Option<String> {
+    state
+        .get(IDENTITY_KEY)
+        .and_then(|nick| serde_json::from_str(nick).ok())
+}
+
+/// Active session as listed to admins
+#[derive(Serialize, sqlx::FromRow)]
+pub(crate) struct SessionInfo {
+    pub id: i64,
+    pub created_at: i64,
+    pub expires_at: i64,
+}
+
+/// Sessions kept in the `sessions` table
+#[derive(Clone)]
+pub(crate) struct SqliteSessionStore {
+    pool: Arc<SqlitePool>,
+}
+
+impl SqliteSessionStore {
+    pub(crate) fn new(pool: Arc<SqlitePool>) -> Self {
+        Self { pool }
+    }
+
+    pub(crate) async fn list_sessions(&self, nick: &str) -> CrateResult<Vec<SessionInfo>> {
+        sqlx::query_as("
+            SELECT ID AS id, created_at, expires_at FROM sessions
+            WHERE user_nick = $1 AND expires_at > $2
+            ORDER BY created_at DESC
+            ")
+            .bind(nick)
+            .bind(unix_now()?)
+            .fetch_all(Arc::as_ref(&self.pool))
+            .await
+            .map_err(Error::server_error)
+    }
+
+    /// Logged user of the session in the request cookie, `None` once the session is gone.
+    /// Admins can delete it while the request is still open.
+    pub(crate) async fn session_user(
+        &self,
+        request: &HttpRequest,
+        key: &Key,
+    ) -> CrateResult<Option<String>> {
+        let Some(cookie) = request.cookie(SESSION_COOKIE) else {
+            return Ok(None);
+        };
+        let mut jar = CookieJar::new();
+        jar.add_original(cookie);
+        let session_key = jar
+            .private(key)
+            .get(SESSION_COOKIE)
+            .and_then(|cookie| SessionKey::try_from(cookie.value().to_string()).ok());
+        let Some(session_key) = session_key else {
+            return Ok(None);
+        };
+
+        let state = self.load(&session_key).await.map_err(Error::server_error)?;
+        Ok(state.as_ref().and_then(session_nick))
+    }
+
+    /// Logs the user out of every device
+    pub(crate) async fn delete_user_sessions(&self, nick: &str) -> CrateResult<()> {
+        sqlx::query("DELETE FROM sessions WHERE user_nick = $1")
+            .bind(nick)
+            .execute(Arc::as_ref(&self.pool))
+            .await
+            .map_err(Error::server_error)?;
+        Ok(())
+    }
+
+    pub(crate) async fn delete_session(&self, nick: &str, id: i64) -> CrateResult<()> {
+        let result = sqlx::query("DELETE FROM sessions WHERE user_nick = $1 AND ID = $2")
+            .bind(nick)
+            .bind(id)
+            .execute(Arc::as_ref(&self.pool))
+            .await
+            .map_err(Error::server_error)?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::session_not_found(id));
+        }
+        Ok(())
+    }
+
+    async fn insert(
+        &self,
+        session_key: &SessionKey,
+        state: &HashMap<String, String>,
+        ttl: &Duration,
+    ) -> anyhow::Result<()> {
+        let now = unix_now()?;
+
+        sqlx::query("DELETE FROM sessions WHERE expires_at <= $1")
+            .bind(now)
+            .execute(Arc::as_ref(&self.pool))
+            .await?;
+
+        sqlx::query("
+            INSERT INTO sessions(session_hash, state, user_nick, created_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            ")
+            .bind(session_hash(session_key))
+            .bind(serde_json::to_string(state)?)
+            .bind(session_nick(state))
+            .bind(now)
+            .bind(now + ttl.whole_seconds())
+            .execute(Arc::as_ref(&self.pool))
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl SessionStore for SqliteSessionStore {
+    async fn load(
+        &self,
+        session_key: &SessionKey,
+    ) -> Result<Option<HashMap<String, String>>, LoadError> {
+        let now = unix_now().map_err(|err| LoadError::Other(err.into()))?;
+        let row: Option<(String,)> = sqlx::query_as(
+            "SELECT state FROM sessions WHERE session_hash = $1 AND expires_at > $2")
+            .bind(session_hash(session_key))
+            .bind(now)
+            .fetch_optional(Arc::as_ref(&self.pool))
+            .await
+            .map_err(|err| LoadError::Other(err.into()))?;
+
+        row.map(|(state,)| serde_json::from_str(&state))
+            .transpose()
+            .map_err(|err| LoadError::Deserialization(err.into()))
+    }
+
+    async fn save(
+        &self,
+        session_state: HashMap<String, String>,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        let session_key = generate_session_key();
+        self.insert(&session_key, &session_state, ttl)
+            .await
+            .map_err(SaveError::Other)?;
+
+        Ok(session_key)
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: HashMap<String, String>,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        let state = serde_json::to_string(&session_state)
+            .map_err(|err| UpdateError::Serialization(err.into()))?;
+        let now = unix_now().map_err(|err| UpdateError::Other(err.into()))?;
+
+        let result = sqlx::query("
+            UPDATE sessions SET state = $2, user_nick = $3, expires_at = $4
+            WHERE session_hash = $1 AND expires_at > $5
+            ")
+            .bind(session_hash(&session_key))
+            .bind(state)
+            .bind(session_nick(&session_state))
+            .bind(now + ttl.whole_seconds())
+            .bind(now)
+            .execute(Arc::as_ref(&self.pool))
+            .await
+            .map_err(|err| UpdateError::Other(err.into()))?;
+
+        // the session was deleted or expired meanwhile, start a new one without the login,
+        // or a request still running would bring back a revoked session
+        if result.rows_affected() == 0 {
+            let mut session_state = session_state;
+            session_state.retain(|key, _| !key.starts_with(IDENTITY_PREFIX));
+            return self.save(session_state, ttl).await.map_err(|err| match err {
+                SaveError::Serialization(err) => UpdateError::Serialization(err),
+                SaveError::Other(err) => UpdateError::Other(err),
+            });
+        }
+
+        Ok(session_key)
+    }
+
+    async fn update_ttl(&self, session_key: &SessionKey, ttl: &Duration) -> anyhow::Result<()> {
+        sqlx::query("UPDATE sessions SET expires_at = $2 WHERE session_hash = $1")
+            .bind(session_hash(session_key))
+            .bind(unix_now()? + ttl.whole_seconds())
+            .execute(Arc::as_ref(&self.pool))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> anyhow::Result<()> {
+        sqlx::query("DELETE FROM sessions WHERE session_hash = $1")
+            .bind(session_hash(session_key))
+            .execute(Arc::as_ref(&self.pool))
+            .await?;
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod session_test {
+    use super::*;
+
+    #[tokio::test]
+    async fn store_sessions_by_user() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        let store = SqliteSessionStore::new(Arc::new(pool));
+        let ttl = Duration::hours(1);
+
+        let key = store.save(HashMap::new(), &ttl).await.unwrap();
+        assert_eq!(store.load(&key).await.unwrap(), Some(HashMap::new()));
+
+        let state = HashMap::from([(IDENTITY_KEY.to_string(), r#""steve""#.to_string())]);
+        let key = store.update(key, state.clone(), &ttl).await.unwrap();
+        assert_eq!(store.load(&key).await.unwrap(), Some(state));
+        assert_eq!(store.list_sessions("steve").await.unwrap().len(), 1);
+
+        store.delete_user_sessions("steve").await.unwrap();
+        assert_eq!(store.load(&key).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn deleted_session_stays_logged_out() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        let store = SqliteSessionStore::new(Arc::new(pool));
+        let ttl = Duration::hours(1);
+
+        let state = HashMap::from([
+            (IDENTITY_KEY.to_string(), r#""steve""#.to_string()),
+            ("actix_identity.visit_deadline".to_string(), "1".to_string()),
+        ]);
+        let key = store.save(state.clone(), &ttl).await.unwrap();
+        store.delete_user_sessions("steve").await.unwrap();
+
+        // a request still running when the session was deleted
+        let key = store.update(key, state, &ttl).await.unwrap();
+        assert_eq!(store.load(&key).await.unwrap(), Some(HashMap::new()));
+        assert!(store.list_sessions("steve").await.unwrap().is_empty());
+    }
+}
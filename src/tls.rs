@@ -0,0 +1,96 @@
+use std::sync::Arc;
+
+use rustls::{
+    crypto::ring,
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer},
+    ServerConfig,
+};
+
+use crate::{
+    config::TlsConfig,
+    error::{CrateResult, Error},
+};
+
+/// Certificate chain with its private key
+type CertifiedKey = (Vec<CertificateDer<'static>>, PrivateKeyDer<'static>);
+
+/// Names of the generated development certificate
+const SELF_SIGNED_NAMES: [&str; 3] = ["localhost", "127.0.0.1", "::1"];
+
+fn self_signed() -> CrateResult<CertifiedKey> {
+    let names = SELF_SIGNED_NAMES.map(String::from).to_vec();
+    let generated = rcgen::generate_simple_self_signed(names).map_err(Error::invalid_config)?;
+
+    let key = PrivatePkcs8KeyDer::from(generated.signing_key.serialize_der());
+    Ok((vec![generated.cert.der().clone()], key.into()))
+}
+
+fn read_pem(tls: &TlsConfig) -> CrateResult<CertifiedKey> {
+    let (Some(cert), Some(key)) = (&tls.cert, &tls.key) else {
+        return Err(Error::invalid_config("tls.cert and tls.key are required"));
+    };
+    let pem_error = |path: &std::path::Path, err: rustls::pki_types::pem::Error| {
+        Error::invalid_config(format!("{}: {err}", path.display()))
+    };
+
+    let certs = CertificateDer::pem_file_iter(cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|err| pem_error(cert, err))?;
+    let key = PrivateKeyDer::from_pem_file(key).map_err(|err| pem_error(key, err))?;
+
+    Ok((certs, key))
+}
+
+/// rustls settings of the HTTPS listener
+pub(crate) fn server_config(tls: &TlsConfig) -> CrateResult<ServerConfig> {
+    let (certs, key) = if tls.self_signed { self_signed()? } else { read_pem(tls)? };
+
+    ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(Error::invalid_config)?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(Error::invalid_config)
+}
+
+/// Removes the port of a `Host` header, IPv6 addresses keep their brackets
+fn strip_port(host: &str) -> &str {
+    match host.rsplit_once(':') {
+        Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => host,
+    }
+}
+
+/// Same URL of the request on the HTTPS listener
+pub(crate) fn https_location(host: &str, https_port: u16, path_and_query: &str) -> String {
+    let host = strip_port(host);
+    match https_port {
+        443 => format!("https://{host}{path_and_query}"),
+        port => format!("https://{host}:{port}{path_and_query}"),
+    }
+}
+
+
+#[cfg(test)]
+mod tls_test {
+    use super::*;
+
+    #[test]
+    fn build_self_signed_config() {
+        let tls = TlsConfig { self_signed: true, ..Default::default() };
+        assert!(server_config(&tls).is_ok());
+
+        assert!(server_config(&TlsConfig::default()).is_err());
+    }
+
+    #[test]
+    fn redirect_to_the_https_port() {
+        assert_eq!(
+            https_location("mc.local:80", 443, "/audit?limit=5"),
+            "https://mc.local/audit?limit=5",
+        );
+        assert_eq!(https_location("mc.local", 6969, "/"), "https://mc.local:6969/");
+        assert_eq!(https_location("[::1]:80", 6969, "/"), "https://[::1]:6969/");
+        assert_eq!(https_location("[::1]", 443, "/"), "https://[::1]/");
+    }
+}
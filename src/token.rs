@@ -0,0 +1,208 @@
+use std::sync::Arc;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use hmac::{Hmac, Mac};
+use jwt::{SignWithKey, VerifyWithKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{types::Json, SqlitePool};
+
+use crate::{
+    clock::unix_now,
+    error::{CrateResult, Error},
+    user::Grant,
+};
+
+#[derive(Serialize, Deserialize)]
+struct TokenClaims {
+    sub: String,
+    jti: String,
+    exp: i64,
+}
+
+/// Token as listed to its owner, without the token itself
+#[derive(Serialize, sqlx::FromRow)]
+pub(crate) struct TokenInfo {
+    pub id: String,
+    pub name: String,
+    pub scopes: Json<Vec<Grant>>,
+    pub created_at: i64,
+    pub expires_at: i64,
+    pub revoked: bool,
+}
+
+/// Returned only once, when the token is created
+#[derive(Serialize)]
+pub(crate) struct NewToken {
+    pub id: String,
+    pub token: String,
+    pub expires_at: i64,
+}
+
+/// Owner and scopes of a valid token
+pub(crate) struct VerifiedToken {
+    pub nick: String,
+    pub scopes: Vec<Grant>,
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn token_hash(token: &str) -> String {
+    to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+/// Personal access tokens: JWTs signed with the secret key, stored hashed in `api_tokens`
+#[derive(Clone)]
+pub(crate) struct TokenManager {
+    pool: Arc<SqlitePool>,
+    key: Hmac<Sha256>,
+}
+
+impl TokenManager {
+    pub(crate) fn new(pool: Arc<SqlitePool>, secret_key: Arc<String>) -> Self {
+        Self {
+            pool,
+            key: Hmac::new_from_slice(secret_key.as_bytes()).expect("hmac accepts any key size"),
+        }
+    }
+
+    /// The scopes must be checked against the permissions of the user by the caller
+    pub(crate) async fn create_token(
+        &self,
+        nick: String,
+        name: String,
+        scopes: Vec<Grant>,
+        ttl_secs: i64,
+    ) -> CrateResult<NewToken> {
+        let mut id = [0; 16];
+        OsRng.fill_bytes(&mut id);
+
+        let now = unix_now()?;
+        let claims = TokenClaims {
+            sub: nick.clone(),
+            jti: to_hex(&id),
+            exp: now + ttl_secs,
+        };
+        let token = (&claims).sign_with_key(&self.key).map_err(Error::server_error)?;
+
+        sqlx::query("
+            INSERT INTO api_tokens(
+                token_id, user_id, name, token_hash, scopes, created_at, expires_at
+            ) VALUES (
+                $1, (SELECT id FROM rcon_users WHERE game_nick = $2), $3, $4, $5, $6, $7
+            );
+            ")
+            .bind(&claims.jti)
+            .bind(&nick)
+            .bind(&name)
+            .bind(token_hash(&token))
+            .bind(Json(&scopes))
+            .bind(now)
+            .bind(claims.exp)
+            .execute(Arc::as_ref(&self.pool))
+            .await
+            .map_err(Error::server_error)?;
+
+        Ok(NewToken { id: claims.jti, token, expires_at: claims.exp })
+    }
+
+    /// Checks the signature, the expiration and that the token wasn't revoked
+    pub(crate) async fn verify(&self, token: &str) -> CrateResult<VerifiedToken> {
+        let claims: TokenClaims = token
+            .verify_with_key(&self.key)
+            .map_err(|_| Error::not_logged_in("invalid token"))?;
+        if claims.exp <= unix_now()? {
+            return Err(Error::not_logged_in("token expired"));
+        }
+
+        let row: Option<(String, Json<Vec<Grant>>)> = sqlx::query_as("
+            SELECT rcon_users.game_nick, api_tokens.scopes FROM api_tokens
+            JOIN rcon_users ON rcon_users.id = api_tokens.user_id
+            WHERE
+                api_tokens.token_hash = $1
+                AND api_tokens.token_id = $2
+                AND api_tokens.revoked = 0
+            ")
+            .bind(token_hash(token))
+            .bind(&claims.jti)
+            .fetch_optional(Arc::as_ref(&self.pool))
+            .await
+            .map_err(Error::server_error)?;
+
+        match row {
+            Some((nick, scopes)) if nick == claims.sub => {
+                Ok(VerifiedToken { nick, scopes: scopes.0 })
+            },
+            _ => Err(Error::not_logged_in("token revoked")),
+        }
+    }
+
+    pub(crate) async fn list_tokens(&self, nick: &str) -> CrateResult<Vec<TokenInfo>> {
+        sqlx::query_as("
+            SELECT
+                token_id AS id, name, scopes, created_at, expires_at, revoked
+            FROM api_tokens
+            WHERE user_id = (SELECT id FROM rcon_users WHERE game_nick = $1)
+            ORDER BY created_at DESC
+            ")
+            .bind(nick)
+            .fetch_all(Arc::as_ref(&self.pool))
+            .await
+            .map_err(Error::server_error)
+    }
+
+    /// Revokes a token of `nick`, any token when `nick` is `None`
+    pub(crate) async fn revoke(&self, id: &str, nick: Option<&str>) -> CrateResult<()> {
+        let result = sqlx::query("
+            UPDATE api_tokens SET revoked = 1
+            WHERE
+                token_id = $1
+                AND ($2 IS NULL OR user_id = (SELECT id FROM rcon_users WHERE game_nick = $2))
+            ")
+            .bind(id)
+            .bind(nick)
+            .execute(Arc::as_ref(&self.pool))
+            .await
+            .map_err(Error::server_error)?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::token_not_found(id));
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod token_test {
+    use super::*;
+
+    #[tokio::test]
+    async fn create_verify_and_revoke() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        sqlx::query("INSERT INTO rcon_users(game_nick, password) VALUES ('steve', 'hash')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let manager = TokenManager::new(Arc::new(pool), Arc::new("@test-secret123".to_string()));
+
+        let scope = Grant { command: "say".to_string(), server: "survival".to_string() };
+        let new = manager
+            .create_token("steve".to_string(), "bot".to_string(), vec![scope.clone()], 60)
+            .await
+            .unwrap();
+
+        let verified = manager.verify(&new.token).await.unwrap();
+        assert_eq!(verified.nick, "steve");
+        assert_eq!(verified.scopes, vec![scope]);
+
+        let other = TokenManager::new(manager.pool.clone(), Arc::new("other".to_string()));
+        assert!(other.verify(&new.token).await.is_err());
+
+        manager.revoke(&new.id, Some("steve")).await.unwrap();
+        assert!(manager.verify(&new.token).await.is_err());
+    }
+}
@@ -0,0 +1,48 @@
+//! Static web UI compiled into the binary, the sources live in `ui/`.
+
+/// File served by `/ui/{name}`
+pub(crate) struct Asset {
+    pub name: &'static str,
+    pub content_type: &'static str,
+    pub body: &'static str,
+}
+
+pub(crate) const INDEX: Asset = Asset {
+    name: "index.html",
+    content_type: "text/html; charset=utf-8",
+    body: include_str!("../ui/index.html"),
+};
+
+static ASSETS: [Asset; 3] = [
+    INDEX,
+    Asset {
+        name: "app.js",
+        content_type: "text/javascript; charset=utf-8",
+        body: include_str!("../ui/app.js"),
+    },
+    Asset {
+        name: "style.css",
+        content_type: "text/css; charset=utf-8",
+        body: include_str!("../ui/style.css"),
+    },
+];
+
+pub(crate) fn asset(name: &str) -> Option<&'static Asset> {
+    ASSETS.iter().find(|asset| asset.name == name)
+}
+
+
+#[cfg(test)]
+mod ui_test {
+    use super::*;
+
+    #[test]
+    fn index_links_every_asset() {
+        for asset in &ASSETS[1..] {
+            assert!(INDEX.body.contains(&format!("/ui/{}", asset.name)), "{}", asset.name);
+        }
+
+        assert!(asset("app.js").is_some());
+        assert!(asset("../Cargo.toml").is_none());
+    }
+}
@@ -1,16 +1,56 @@
 use std::sync::Arc;
 
+use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
 
 use crate::error::{CrateResult, Error};
+use crate::permission;
 
-pub(crate) struct UserManager {
+/// Server scope of permissions granted on every server
+pub(crate) const ALL_SERVERS: &str = "*";
+
+/// Command pattern granted on a server, or on [`ALL_SERVERS`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Grant {
+    pub command: String,
+    pub server: String,
+}
+
+impl Grant {
+    /// Tells if the server applies to this grant
+    pub(crate) fn applies_to(&self, server: &str) -> bool {
+        self.server == ALL_SERVERS || self.server == server
+    }
+
+    /// Tells if every command line allowed to `nick` by `narrower` is allowed by this grant,
+    /// like the scope `tp {self} *` under the grant `tp`
+    pub(crate) fn covers(&self, narrower: &Grant, nick: &str) -> bool {
+        let pattern = narrower.command.replace(permission::SELF_TOKEN, nick);
+        self.applies_to(&narrower.server) && permission::matches(&self.command, &pattern, nick)
+    }
+}
+
+/// Named group of permissions assigned to users
+#[derive(Debug, Serialize)]
+pub(crate) struct Role {
+    pub name: String,
+    pub permissions: Vec<Grant>,
+}
+
+/// User as listed to admins
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub(crate) struct UserInfo {
+    pub nick: String,
+    pub disabled: bool,
+}
+
+pub struct UserManager {
     pool: Arc<SqlitePool>,
 }
 
 
 impl UserManager {
-    pub(crate) fn new(pool: Arc<SqlitePool>) -> Self {
+    pub fn new(pool: Arc<SqlitePool>) -> Self {
         Self {
             pool
         }
@@ -27,16 +67,24 @@ impl UserManager {
         Ok(())
     }
     
-    pub(crate) async fn add_user_permissions(&self, nick: String, permissions: Vec<String>) -> CrateResult<()> {
+    /// Grants the commands on `server`, [`ALL_SERVERS`] grants them everywhere
+    pub(crate) async fn add_user_permissions(
+        &self,
+        nick: String,
+        server: String,
+        permissions: Vec<String>,
+    ) -> CrateResult<()> {
         for p in permissions {
             sqlx::query("
-                INSERT INTO users_permissions(user_id, command) VALUES(
+                INSERT INTO users_permissions(user_id, command, server) VALUES(
                   (SELECT id FROM rcon_users WHERE game_nick = $1),
-                  $2
+                  $2,
+                  $3
                 );
                 ")
                 .bind(nick.clone())
                 .bind(p.clone())
+                .bind(server.clone())
                 .execute(Arc::as_ref(&self.pool))
                 .await
                 .map_err(Error::cant_create_user)?;
@@ -45,7 +93,7 @@ impl UserManager {
         Ok(())
     }
     
-    pub(crate) async fn create_super_user(&self, root_password: String) -> CrateResult<()> {
+    pub async fn create_super_user(&self, root_password: String) -> CrateResult<()> {
         let tx = self.pool.begin().await.unwrap();
         
         let result = sqlx::query("            
@@ -74,69 +122,374 @@ impl UserManager {
         }
     }
     
-    pub(crate) async fn has_permissions(&self, nick: String, permission: String) -> CrateResult<()> {
+    /// Fails for deleted and disabled users, used to reject the sessions they still hold
+    pub(crate) async fn ensure_active(&self, nick: &str) -> CrateResult<()> {
+        let row: Option<(bool,)> = sqlx::query_as(
+            "SELECT disabled FROM rcon_users WHERE game_nick = $1")
+            .bind(nick)
+            .fetch_optional(Arc::as_ref(&self.pool))
+            .await
+            .map_err(Error::server_error)?;
+        
+        match row {
+            Some((false,)) => Ok(()),
+            Some((true,)) => Err(Error::user_disabled(nick)),
+            None => Err(Error::user_not_found(nick)),
+        }
+    }
+    
+    pub(crate) async fn set_disabled(&self, nick: String, disabled: bool) -> CrateResult<()> {
+        let result = sqlx::query("UPDATE rcon_users SET disabled = $2 WHERE game_nick = $1")
+            .bind(&nick)
+            .bind(disabled)
+            .execute(Arc::as_ref(&self.pool))
+            .await
+            .map_err(Error::server_error)?;
+        
+        if result.rows_affected() == 0 {
+            return Err(Error::user_not_found(nick));
+        }
+        Ok(())
+    }
+    
+    /// `password` must be already hashed, see [`crate::password::PasswordManager`]
+    pub(crate) async fn set_password(&self, nick: String, password: String) -> CrateResult<()> {
+        let result = sqlx::query("UPDATE rcon_users SET password = $2 WHERE game_nick = $1")
+            .bind(&nick)
+            .bind(password)
+            .execute(Arc::as_ref(&self.pool))
+            .await
+            .map_err(Error::server_error)?;
+        
+        if result.rows_affected() == 0 {
+            return Err(Error::user_not_found(nick));
+        }
+        Ok(())
+    }
+    
+    pub(crate) async fn list_users(&self) -> CrateResult<Vec<UserInfo>> {
+        sqlx::query_as("SELECT game_nick AS nick, disabled FROM rcon_users ORDER BY game_nick")
+            .fetch_all(Arc::as_ref(&self.pool))
+            .await
+            .map_err(Error::server_error)
+    }
+    
+    /// Removes the user with its permissions, role assignments and tokens
+    pub(crate) async fn delete_user(&self, nick: String) -> CrateResult<()> {
+        let mut tx = self.pool.begin().await.map_err(Error::server_error)?;
+        
+        for query in [
+            "DELETE FROM users_permissions
+             WHERE user_id = (SELECT id FROM rcon_users WHERE game_nick = $1)",
+            "DELETE FROM users_roles
+             WHERE user_id = (SELECT id FROM rcon_users WHERE game_nick = $1)",
+            "DELETE FROM api_tokens
+             WHERE user_id = (SELECT id FROM rcon_users WHERE game_nick = $1)",
+        ] {
+            sqlx::query(query)
+                .bind(&nick)
+                .execute(&mut *tx)
+                .await
+                .map_err(Error::server_error)?;
+        }
+        
+        let result = sqlx::query("DELETE FROM rcon_users WHERE game_nick = $1")
+            .bind(&nick)
+            .execute(&mut *tx)
+            .await
+            .map_err(Error::server_error)?;
+        if result.rows_affected() == 0 {
+            return Err(Error::user_not_found(nick));
+        }
+        
+        tx.commit().await.map_err(Error::server_error)
+    }
+    
+    /// Removes a direct grant, permissions given through roles are kept
+    pub(crate) async fn revoke_user_permission(
+        &self,
+        nick: String,
+        server: String,
+        permission: String,
+    ) -> CrateResult<()> {
         let result = sqlx::query("
-            SELECT * FROM users_permissions
+            DELETE FROM users_permissions
             WHERE
                 user_id = (SELECT id FROM rcon_users WHERE game_nick = $1)
-                AND users_permissions.command = $2
+                AND command = $2
+                AND server = $3
             ")
             .bind(&nick)
             .bind(&permission)
+            .bind(&server)
+            .execute(Arc::as_ref(&self.pool))
+            .await
+            .map_err(Error::server_error)?;
+        
+        if result.rows_affected() > 0 {
+            return Ok(());
+        }
+        let user: Option<(i64,)> = sqlx::query_as("SELECT id FROM rcon_users WHERE game_nick = $1")
+            .bind(&nick)
             .fetch_optional(Arc::as_ref(&self.pool))
             .await
-            .map_err(Error::cant_create_user)?;           
+            .map_err(Error::server_error)?;
+        match user {
+            Some(_) => Err(Error::permission_not_found(format!("{permission} on {server}"))),
+            None => Err(Error::user_not_found(nick)),
+        }
+    }
+    
+    /// Checks a grant on `server` or on [`ALL_SERVERS`], directly or through a role
+    pub(crate) async fn has_permissions(
+        &self,
+        nick: String,
+        server: &str,
+        permission: String,
+    ) -> CrateResult<()> {
+        let permissions = self.permissions(&nick, server).await?;
         
-        if let Some(_) = result {
+        if permissions.contains(&permission) {
             Ok(())
         }else {
-            Err(Error::DontHavePermission { raw_err: nick })
+            Err(Error::dont_have_permission(nick))
         }
     }
+    
+    /// Effective permission patterns of `nick` valid on `server`, granted directly or
+    /// through the roles of the user. Disabled users don't have any.
+    pub(crate) async fn permissions(&self, nick: &str, server: &str) -> CrateResult<Vec<String>> {
+        let rows: Vec<(String,)> = sqlx::query_as("
+            SELECT command FROM users_permissions
+            WHERE
+                user_id = (SELECT id FROM rcon_users WHERE game_nick = $1 AND disabled = 0)
+                AND users_permissions.server IN ('*', $2)
+            UNION
+            SELECT role_permissions.command FROM role_permissions
+            JOIN users_roles ON users_roles.role_id = role_permissions.role_id
+            WHERE
+                users_roles.user_id = (
+                    SELECT id FROM rcon_users WHERE game_nick = $1 AND disabled = 0
+                )
+                AND role_permissions.server IN ('*', $2)
+            ")
+            .bind(nick)
+            .bind(server)
+            .fetch_all(Arc::as_ref(&self.pool))
+            .await
+            .map_err(Error::server_error)?;
+        
+        Ok(rows.into_iter().map(|row| row.0).collect())
+    }
+    
+    /// Every grant of an active user on any server, directly or through roles
+    pub(crate) async fn grants(&self, nick: &str) -> CrateResult<Vec<Grant>> {
+        let rows: Vec<(String, String)> = sqlx::query_as("
+            SELECT command, server FROM users_permissions
+            WHERE user_id = (SELECT id FROM rcon_users WHERE game_nick = $1 AND disabled = 0)
+            UNION
+            SELECT role_permissions.command, role_permissions.server FROM role_permissions
+            JOIN users_roles ON users_roles.role_id = role_permissions.role_id
+            WHERE users_roles.user_id = (
+                SELECT id FROM rcon_users WHERE game_nick = $1 AND disabled = 0
+            )
+            ORDER BY 2, 1
+            ")
+            .bind(nick)
+            .fetch_all(Arc::as_ref(&self.pool))
+            .await
+            .map_err(Error::server_error)?;
+        
+        Ok(rows.into_iter().map(|(command, server)| Grant { command, server }).collect())
+    }
+    
+    /// Checks the whole command line against the permission patterns of `nick`,
+    /// see [`crate::permission`]
+    pub(crate) async fn can_execute(
+        &self,
+        nick: String,
+        server: &str,
+        command_line: &str,
+    ) -> CrateResult<()> {
+        let patterns = self.permissions(&nick, server).await?;
+        
+        if patterns.iter().any(|p| permission::matches(p, command_line, &nick)) {
+            Ok(())
+        } else {
+            Err(Error::dont_have_permission(nick))
+        }
+    }
+    
+    /// Creates the role when it doesn't exist and grants the commands to it
+    pub(crate) async fn add_role_permissions(
+        &self,
+        role: String,
+        server: String,
+        permissions: Vec<String>,
+    ) -> CrateResult<()> {
+        let mut tx = self.pool.begin().await.map_err(Error::server_error)?;
+        
+        sqlx::query("INSERT OR IGNORE INTO roles(name) VALUES ($1);")
+            .bind(&role)
+            .execute(&mut *tx)
+            .await
+            .map_err(Error::server_error)?;
+        
+        for p in permissions {
+            sqlx::query("
+                INSERT OR IGNORE INTO role_permissions(role_id, command, server) VALUES(
+                  (SELECT id FROM roles WHERE name = $1),
+                  $2,
+                  $3
+                );
+                ")
+                .bind(&role)
+                .bind(&p)
+                .bind(&server)
+                .execute(&mut *tx)
+                .await
+                .map_err(Error::server_error)?;
+        }
+        
+        tx.commit().await.map_err(Error::server_error)
+    }
+    
+    pub(crate) async fn list_roles(&self) -> CrateResult<Vec<Role>> {
+        let rows: Vec<(String, Option<String>, Option<String>)> = sqlx::query_as("
+            SELECT roles.name, role_permissions.command, role_permissions.server
+            FROM roles
+            LEFT JOIN role_permissions ON role_permissions.role_id = roles.id
+            ORDER BY roles.name, role_permissions.command
+            ")
+            .fetch_all(Arc::as_ref(&self.pool))
+            .await
+            .map_err(Error::server_error)?;
+        
+        let mut roles: Vec<Role> = Vec::new();
+        for (name, command, server) in rows {
+            if roles.last().is_none_or(|role| role.name != name) {
+                roles.push(Role { name, permissions: Vec::new() });
+            }
+            if let (Some(command), Some(server), Some(role)) = (command, server, roles.last_mut()) {
+                role.permissions.push(Grant { command, server });
+            }
+        }
+        
+        Ok(roles)
+    }
+    
+    /// Fails with [`Error::UserNotFound`] or [`Error::RoleNotFound`] without assigning any
+    /// of the roles
+    pub(crate) async fn assign_roles(&self, nick: String, roles: Vec<String>) -> CrateResult<()> {
+        let mut tx = self.pool.begin().await.map_err(Error::server_error)?;
+        
+        let user: Option<(i64,)> = sqlx::query_as("SELECT id FROM rcon_users WHERE game_nick = $1")
+            .bind(&nick)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(Error::server_error)?;
+        let Some((user_id,)) = user else {
+            return Err(Error::user_not_found(nick));
+        };
+        
+        for role in roles {
+            let found: Option<(i64,)> = sqlx::query_as("SELECT id FROM roles WHERE name = $1")
+                .bind(&role)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(Error::server_error)?;
+            let Some((role_id,)) = found else {
+                return Err(Error::role_not_found(role));
+            };
+            
+            sqlx::query("
+                INSERT INTO users_roles(user_id, role_id) VALUES($1, $2)
+                ON CONFLICT DO NOTHING;
+                ")
+                .bind(user_id)
+                .bind(role_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(Error::server_error)?;
+        }
+        
+        tx.commit().await.map_err(Error::server_error)
+    }
+    
+    pub(crate) async fn unassign_role(&self, nick: String, role: String) -> CrateResult<()> {
+        let result = sqlx::query("
+            DELETE FROM users_roles
+            WHERE
+                user_id = (SELECT id FROM rcon_users WHERE game_nick = $1)
+                AND role_id = (SELECT id FROM roles WHERE name = $2)
+            ")
+            .bind(&nick)
+            .bind(&role)
+            .execute(Arc::as_ref(&self.pool))
+            .await
+            .map_err(Error::server_error)?;
+        
+        if result.rows_affected() > 0 {
+            return Ok(());
+        }
+        let user: Option<(i64,)> = sqlx::query_as("SELECT id FROM rcon_users WHERE game_nick = $1")
+            .bind(&nick)
+            .fetch_optional(Arc::as_ref(&self.pool))
+            .await
+            .map_err(Error::server_error)?;
+        match user {
+            Some(_) => Err(Error::role_not_found(format!("{role} of {nick}"))),
+            None => Err(Error::user_not_found(nick)),
+        }
+    }
+    
+    pub(crate) async fn user_roles(&self, nick: String) -> CrateResult<Vec<String>> {
+        let rows: Vec<(String,)> = sqlx::query_as("
+            SELECT roles.name FROM roles
+            JOIN users_roles ON users_roles.role_id = roles.id
+            WHERE users_roles.user_id = (SELECT id FROM rcon_users WHERE game_nick = $1)
+            ORDER BY roles.name
+            ")
+            .bind(&nick)
+            .fetch_all(Arc::as_ref(&self.pool))
+            .await
+            .map_err(Error::server_error)?;
+        
+        Ok(rows.into_iter().map(|row| row.0).collect())
+    }
 }
 
 
 //TODO: use #[sqlx::test] https://docs.rs/sqlx/latest/sqlx/attr.test.html#supported-databases
 #[cfg(test)]
 mod user_manager_test {
-    use sqlx::{pool::PoolOptions, Sqlite};
-
     use super::*;
-    use std::sync::OnceLock;
-    static POOL: OnceLock<SqlitePool> = OnceLock::new();
-    
-    async fn migrate() {        
-        let pool = match POOL.get() {
-            Some(p) => p,
-            None => {
-                println!("creating new pool");
-                let pool = SqlitePool::connect("sqlite::memory:")
-                    .await
-                    .expect("should create new pool");
-                
-                if let Err(_) = POOL.set(pool) {
-                    return;
-                }else {
-                    POOL.get().unwrap()
-                }
-            }
-        };
+    
+    /// Every test gets its own in-memory database
+    async fn migrate() -> Arc<SqlitePool> {        
+        let pool = SqlitePool::connect("sqlite::memory:")
+            .await
+            .expect("should create new pool");
         
         sqlx::migrate!("./migrations")
-            .run(pool)
+            .run(&pool)
             .await
             .expect("should run the migrations");
+        
+        Arc::new(pool)
     }
         
     #[tokio::test]
     async fn create_and_add_permissions() {
-        migrate().await;
-        
-        let pool = Arc::new(POOL.get().expect("should get POOL").clone());
+        let pool = migrate().await;
         let manager = UserManager::new(pool.clone());
         
         manager.new_user("steve".to_string(), "hasshed@password".to_string()).await.unwrap();
-        manager.add_user_permissions("steve".to_string(), vec!["say".to_string()]).await.unwrap();
+        manager.add_user_permissions(
+            "steve".to_string(),
+            ALL_SERVERS.to_string(),
+            vec!["say".to_string()],
+        ).await.unwrap();
         
         let permissions: (String,) = sqlx::query_as("SELECT command FROM users_permissions")
             .bind("steve".to_string())
@@ -146,4 +499,173 @@ mod user_manager_test {
         
         assert_eq!(permissions.0, "say");
     }
-}
\ No newline at end of file
+    
+    #[tokio::test]
+    async fn permissions_scoped_by_server() {
+        let pool = migrate().await;
+        let manager = UserManager::new(pool.clone());
+        
+        manager.new_user("alex".to_string(), "hasshed@password".to_string()).await.unwrap();
+        manager.add_user_permissions(
+            "alex".to_string(),
+            "creative".to_string(),
+            vec!["tp".to_string()],
+        ).await.unwrap();
+        
+        let alex = || "alex".to_string();
+        assert!(manager.has_permissions(alex(), "creative", "tp".to_string()).await.is_ok());
+        assert!(manager.has_permissions(alex(), "survival", "tp".to_string()).await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn can_execute_with_patterns() {
+        let pool = migrate().await;
+        let manager = UserManager::new(pool.clone());
+        
+        manager.new_user("steve".to_string(), "hasshed@password".to_string()).await.unwrap();
+        manager.add_user_permissions(
+            "steve".to_string(),
+            ALL_SERVERS.to_string(),
+            vec!["tp {self} *".to_string()],
+        ).await.unwrap();
+        
+        let steve = || "steve".to_string();
+        assert!(manager.can_execute(steve(), "default", "/tp steve alex").await.is_ok());
+        assert!(manager.can_execute(steve(), "default", "/tp alex steve").await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn permissions_through_roles() {
+        let pool = migrate().await;
+        let manager = UserManager::new(pool.clone());
+        let steve = || "steve".to_string();
+        
+        manager.new_user(steve(), "hasshed@password".to_string()).await.unwrap();
+        manager.add_role_permissions(
+            "moderator".to_string(),
+            ALL_SERVERS.to_string(),
+            vec!["kick *".to_string(), "say".to_string()],
+        ).await.unwrap();
+        
+        assert!(manager.can_execute(steve(), "default", "/kick alex").await.is_err());
+        
+        manager.assign_roles(steve(), vec!["moderator".to_string()]).await.unwrap();
+        
+        assert!(manager.can_execute(steve(), "default", "/kick alex").await.is_ok());
+        assert!(manager.has_permissions(steve(), "default", "say".to_string()).await.is_ok());
+        assert_eq!(manager.user_roles(steve()).await.unwrap(), vec!["moderator"]);
+        assert_eq!(manager.list_roles().await.unwrap()[0].permissions.len(), 2);
+    }
+    
+    #[tokio::test]
+    async fn assign_unknown_roles_and_users() {
+        let pool = migrate().await;
+        let manager = UserManager::new(pool.clone());
+        let steve = || "steve".to_string();
+        let roles = || vec!["moderator".to_string(), "admin".to_string()];
+        
+        manager.new_user(steve(), "hasshed@password".to_string()).await.unwrap();
+        manager.add_role_permissions(
+            "moderator".to_string(),
+            ALL_SERVERS.to_string(),
+            vec!["kick *".to_string()],
+        ).await.unwrap();
+        
+        let unknown_role = manager.assign_roles(steve(), roles()).await;
+        assert!(matches!(unknown_role, Err(Error::RoleNotFound { .. })));
+        assert!(manager.user_roles(steve()).await.unwrap().is_empty());
+        let unknown_user = manager.assign_roles("alex".to_string(), roles()).await;
+        assert!(matches!(unknown_user, Err(Error::UserNotFound { .. })));
+    }
+    
+    #[tokio::test]
+    async fn unassign_missing_roles() {
+        let pool = migrate().await;
+        let manager = UserManager::new(pool.clone());
+        let steve = || "steve".to_string();
+        let moderator = || "moderator".to_string();
+        
+        manager.new_user(steve(), "hasshed@password".to_string()).await.unwrap();
+        manager.add_role_permissions(moderator(), ALL_SERVERS.to_string(), vec!["say".to_string()])
+            .await
+            .unwrap();
+        manager.assign_roles(steve(), vec![moderator()]).await.unwrap();
+        
+        manager.unassign_role(steve(), moderator()).await.unwrap();
+        let again = manager.unassign_role(steve(), moderator()).await;
+        assert!(matches!(again, Err(Error::RoleNotFound { .. })));
+        let unknown = manager.unassign_role("alex".to_string(), moderator()).await;
+        assert!(matches!(unknown, Err(Error::UserNotFound { .. })));
+    }
+    
+    #[tokio::test]
+    async fn revoke_disable_and_delete() {
+        let pool = migrate().await;
+        let manager = UserManager::new(pool.clone());
+        let steve = || "steve".to_string();
+        
+        manager.new_user(steve(), "hasshed@password".to_string()).await.unwrap();
+        manager.add_user_permissions(
+            steve(),
+            ALL_SERVERS.to_string(),
+            vec!["say".to_string(), "me".to_string()],
+        ).await.unwrap();
+        
+        manager.revoke_user_permission(steve(), ALL_SERVERS.to_string(), "say".to_string())
+            .await
+            .unwrap();
+        assert!(manager.has_permissions(steve(), "default", "say".to_string()).await.is_err());
+        assert!(manager.has_permissions(steve(), "default", "me".to_string()).await.is_ok());
+        
+        let again = manager.revoke_user_permission(steve(), ALL_SERVERS.to_string(), "say".into());
+        assert!(matches!(again.await, Err(Error::PermissionNotFound { .. })));
+        let unknown = manager.revoke_user_permission("alex".into(), ALL_SERVERS.into(), "me".into());
+        assert!(matches!(unknown.await, Err(Error::UserNotFound { .. })));
+        
+        manager.set_disabled(steve(), true).await.unwrap();
+        assert!(matches!(manager.ensure_active("steve").await, Err(Error::UserDisabled { .. })));
+        assert!(manager.has_permissions(steve(), "default", "me".to_string()).await.is_err());
+        
+        manager.delete_user(steve()).await.unwrap();
+        assert!(matches!(manager.ensure_active("steve").await, Err(Error::UserNotFound { .. })));
+    }
+    
+    #[tokio::test]
+    async fn list_users_and_set_password() {
+        let pool = migrate().await;
+        let manager = UserManager::new(pool.clone());
+        
+        manager.new_user("steve".to_string(), "old@hash".to_string()).await.unwrap();
+        manager.set_password("steve".to_string(), "new@hash".to_string()).await.unwrap();
+        assert!(matches!(
+            manager.set_password("alex".to_string(), "hash".to_string()).await,
+            Err(Error::UserNotFound { .. }),
+        ));
+        
+        let users = manager.list_users().await.unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].nick, "steve");
+        assert!(!users[0].disabled);
+        
+        let (password,): (String,) = sqlx::query_as(
+            "SELECT password FROM rcon_users WHERE game_nick = 'steve'")
+            .fetch_one(pool.as_ref())
+            .await
+            .unwrap();
+        assert_eq!(password, "new@hash");
+    }    
+    #[test]
+    fn grants_cover_narrower_scopes() {
+        let grant = |command: &str, server: &str| Grant {
+            command: command.to_string(),
+            server: server.to_string(),
+        };
+        
+        assert!(grant("tp", "*").covers(&grant("tp {self} *", "creative"), "steve"));
+        assert!(grant("tp {self} *", "*").covers(&grant("tp {self} *", "*"), "steve"));
+        assert!(!grant("tp", "creative").covers(&grant("tp", "survival"), "steve"));
+        assert!(!grant("tp", "creative").covers(&grant("tp", ALL_SERVERS), "steve"));
+        assert!(!grant("tp {self} *", "*").covers(&grant("tp", "*"), "steve"));
+        assert!(!grant("tp alex *", "*").covers(&grant("tp * *", "*"), "steve"));
+    }
+}
@@ -1,50 +1,70 @@
-use std::{io, sync::Arc, time::Duration};
+use std::{io, net::ToSocketAddrs, sync::Arc, time::{Duration, Instant}};
 
 use actix_identity::{Identity, IdentityMiddleware};
-use actix_session::{config::PersistentSession, storage::CookieSessionStore, SessionMiddleware};
+use actix_session::{config::PersistentSession, SessionMiddleware};
 use actix_web::{
-    cookie::Key, get, 
+    cookie::Key, delete, get, http::header,
     middleware::Logger, post, 
     web::{self, Data}, 
-    App, HttpMessage, HttpRequest, HttpResponse, HttpServer, Responder
+    App, FromRequest, HttpMessage, HttpRequest, HttpResponse, HttpServer, Responder
 };
 use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
+use tokio::sync::watch;
 
 use crate::{
+    audit::{AuditFilter, AuditLog, AuditRecord},
+    commands::{Command, Raw},
+    config::{HttpConfig, TlsConfig},
     error::{
         CrateResult, Error
     }, 
-    rcon::RconConnection
+    ping,
+    query::QueryClient,
+    rcon::{ConnectionState, RconResponse},
+    registry::{RconTarget, ServerRegistry, DEFAULT_SERVER},
+    session::{self, SqliteSessionStore},
+    tls, ui,
 };
 
+use crate::auth::Caller;
 use crate::password::{PasswordManager};
-use crate::user::{UserManager};
+use crate::token::TokenManager;
+use crate::user::{Grant, UserManager, ALL_SERVERS};
 
 
-pub async fn run_server<'a>(
+pub async fn run_server(
     pool: SqlitePool,
     pass_manager: PasswordManager,
-    rcon: RconConnection,
+    registry: ServerRegistry,
     user_manager: UserManager,
+    secret_key: Arc<String>,
+    http: HttpConfig,
+    tls: Option<TlsConfig>,
 ) -> io::Result<()> {
-    let session_secret_key = Key::generate();    
-    let expiration = Duration::from_secs(24 * 60 * 60);
+    let expiration = Duration::from_secs(http.session_ttl);
+    let secure = tls.is_some();
+    let session_secret_key = session::session_key(&secret_key);
+    let token_manager = TokenManager::new(Arc::new(pool.clone()), secret_key);
         
-    let rcon_app_data = web::Data::new(rcon);
+    let registry_data = web::Data::new(registry);
     let user_manager_data = web::Data::new(user_manager);
+    let token_manager_data = web::Data::new(token_manager);
+    let session_store = SqliteSessionStore::new(Arc::new(pool.clone()));
+    let session_store_data = web::Data::new(session_store.clone());
+    let cookie_key_data = web::Data::new(session_secret_key.clone());
+    let audit_data = web::Data::new(AuditLog::new(Arc::new(pool)));
+    let origins_data = web::Data::new(AllowedOrigins(http.allowed_origins.clone()));
     
-    HttpServer::new(move || {
+    let server = HttpServer::new(move || {
         // keep app_data here to avoid being drop outside
         let pass_manager = web::Data::new(pass_manager.clone());
         
-        //TODO: make own impl of session store to save into database.
-        let session_store = CookieSessionStore::default();
-        
         let session_mw =
-            SessionMiddleware::builder(session_store, session_secret_key.clone())
-                // disable secure cookie for local testing
-                .cookie_secure(false)
+            SessionMiddleware::builder(session_store.clone(), session_secret_key.clone())
+                .cookie_name(session::SESSION_COOKIE.to_string())
+                // plain HTTP is only meant for local testing
+                .cookie_secure(secure)
                 // Set a ttl for the cookie if the identity should live longer than the user session
                 .session_lifecycle(
                     PersistentSession::default()
@@ -58,35 +78,125 @@ pub async fn run_server<'a>(
         App::new()
             .wrap(Logger::default())
             .app_data(Data::clone(&pass_manager))
-            .app_data(Data::clone(&rcon_app_data))
+            .app_data(Data::clone(&registry_data))
             .app_data(Data::clone(&user_manager_data))
+            .app_data(Data::clone(&token_manager_data))
+            .app_data(Data::clone(&session_store_data))
+            .app_data(Data::clone(&cookie_key_data))
+            .app_data(Data::clone(&audit_data))
+            .app_data(Data::clone(&origins_data))
             .wrap(identity_mw)
             .wrap(session_mw)
             .service(index)
+            .service(ui_asset)
+            .service(me)
             .service(login)
             .service(logout)
             .service(rcon_command)
+            .service(rcon_status)
+            .service(rcon_ws)
+            .service(server_rcon_command)
+            .service(server_rcon_status)
+            .service(server_status)
+            .service(list_servers)
+            .service(add_server)
+            .service(list_users)
             .service(create_user)
+            .service(user_permissions)
             .service(add_permissions)
+            .service(create_role)
+            .service(list_roles)
+            .service(assign_roles)
+            .service(unassign_role)
+            .service(user_roles)
+            .service(revoke_permission)
+            .service(delete_user)
+            .service(disable_user)
+            .service(enable_user)
+            .service(audit_log)
+            .service(create_token)
+            .service(list_tokens)
+            .service(revoke_token)
+            .service(list_sessions)
+            .service(kill_user_sessions)
+            .service(kill_session)
     })
-    .bind(("127.0.0.1", 6969))
-    .unwrap()
-    .workers(2)
-    .run()
-    .await
+    .workers(http.workers);
+    
+    let Some(tls) = tls else {
+        return server.bind(http.bind.as_str())?.run().await;
+    };
+    
+    let server_config = tls::server_config(&tls).map_err(io::Error::other)?;
+    let server = server.bind_rustls_0_23(http.bind.as_str(), server_config)?.run();
+    let Some(redirect_bind) = tls.redirect_bind else {
+        return server.await;
+    };
+    
+    let https_port = server_port(&http.bind)?;
+    let redirect = HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::new(https_port))
+            .default_service(web::to(redirect_to_https))
+    })
+    .workers(1)
+    .bind(redirect_bind)?
+    .run();
+    
+    tokio::try_join!(server, redirect).map(|_| ())
 }
 
+fn server_port(bind: &str) -> io::Result<u16> {
+    bind.to_socket_addrs()?
+        .next()
+        .map(|addr| addr.port())
+        .ok_or_else(|| io::Error::other(format!("can't resolve {bind}")))
+}
+
+async fn redirect_to_https(req: HttpRequest, https_port: web::Data<u16>) -> impl Responder {
+    let path = req.uri().path_and_query().map_or("/", |path| path.as_str());
+    let location = tls::https_location(req.connection_info().host(), **https_port, path);
+    
+    HttpResponse::PermanentRedirect()
+        .insert_header((header::LOCATION, location))
+        .finish()
+}
 
 
+
+fn asset_response(asset: &ui::Asset) -> HttpResponse {
+    HttpResponse::Ok().content_type(asset.content_type).body(asset.body)
+}
+
 #[get("/")]
-async fn index(user: Option<Identity>) -> impl Responder {
-    if let Some(user) = user {
-        format!("Welcome! {}", user.id().unwrap())
-    } else {
-        "Welcome Anonymous!".to_owned()
+async fn index() -> impl Responder {
+    asset_response(&ui::INDEX)
+}
+
+#[get("/ui/{name}")]
+async fn ui_asset(name: web::Path<String>) -> impl Responder {
+    match ui::asset(&name) {
+        Some(asset) => asset_response(asset),
+        None => HttpResponse::NotFound().finish(),
     }
 }
 
+#[derive(Serialize)]
+struct Me {
+    nick: String,
+    admin: bool,
+    permissions: Vec<Grant>,
+}
+
+/// Who is logged in, used by the UI to offer only the allowed commands
+#[get("/me")]
+async fn me(caller: Caller, user_manager: web::Data<UserManager>) -> CrateResult<impl Responder> {
+    let admin = caller.require_admin(&user_manager).await.is_ok();
+    let permissions = user_manager.grants(&caller.nick).await?;
+    
+    Ok(web::Json(Me { nick: caller.nick, admin, permissions }))
+}
+
 
 #[derive(Deserialize, Serialize)]
 struct LoginData {
@@ -113,7 +223,7 @@ async fn login(
         }
     }
     
-    Identity::login(&request.extensions(), data.user.clone().into()).unwrap();
+    Identity::login(&request.extensions(), data.user.clone()).unwrap();
 
     HttpResponse::Ok()    
     
@@ -131,27 +241,368 @@ struct RconCommandRequest {
     args: Vec<String>,
 }
 
+impl RconCommandRequest {
+    /// Full command line as sent to the RCON server
+    fn render(&self) -> CrateResult<String> {
+        Ok(Raw::new(&self.command, &self.args)?.render())
+    }
+}
+
+/// Checks the permission of the caller on `server` before running the command there,
+/// every attempt is written to the audit log.
+async fn exec_rcon_command(
+    request: &HttpRequest,
+    caller: CrateResult<Caller>,
+    server: &str,
+    command: &RconCommandRequest,
+    registry: &ServerRegistry,
+    user_manager: &UserManager,
+    audit: &AuditLog,
+) -> CrateResult<RconResponse> {
+    let started = Instant::now();
+    let mut record = AuditRecord::new(
+        caller.as_ref().ok().map(|caller| caller.nick.clone()),
+        request.peer_addr().map(|addr| addr.ip().to_string()),
+        server,
+        &command.command,
+        &command.args,
+    );
+    
+    let result = async {
+        let command_line = command.render()?;
+        caller?.can_execute(user_manager, server, &command_line).await?;
+        record.allowed = true;
+        
+        registry.get(server).await?.exec_command(command_line).await
+    }.await;
+    
+    record.finish(&result, started.elapsed());
+    if let Err(err) = audit.record(&record).await {
+        println!("can't write audit record: {}", err);
+    }
+    
+    result
+}
+
 #[post("/rcon/command")]
 async fn rcon_command(
-    user: Option<Identity>, 
-    rcon: web::Data<RconConnection>,
+    request: HttpRequest,
+    caller: CrateResult<Caller>,
+    registry: web::Data<ServerRegistry>,
+    command: web::Json<RconCommandRequest>,
+    user_manager: web::Data<UserManager>,
+    audit: web::Data<AuditLog>,
+) -> CrateResult<impl Responder> {
+    let response = exec_rcon_command(
+        &request, caller, DEFAULT_SERVER, &command, &registry, &user_manager, &audit).await?;
+    
+    Ok(web::Json(response))
+}
+
+#[post("/servers/{name}/rcon/command")]
+async fn server_rcon_command(
+    request: HttpRequest,
+    caller: CrateResult<Caller>,
+    server: web::Path<String>,
+    registry: web::Data<ServerRegistry>,
     command: web::Json<RconCommandRequest>,
     user_manager: web::Data<UserManager>,
-) -> impl Responder {
-    let identity = user.expect("logged user");
-    let nick = identity.id().unwrap();
-    
-    if let Ok(_) = user_manager.has_permissions(nick.clone(), command.command.to_string()).await {
-        //TODO: handle all kinds of commands
-        rcon.exec_command(format!("/{} {}", command.command, command.args[0]))
-            .await
-            .unwrap();
-        HttpResponse::Ok()
-    } else {
-        HttpResponse::Unauthorized()
+    audit: web::Data<AuditLog>,
+) -> CrateResult<impl Responder> {
+    let response = exec_rcon_command(
+        &request, caller, &server, &command, &registry, &user_manager, &audit).await?;
+    
+    Ok(web::Json(response))
+}
+
+#[derive(Deserialize)]
+struct WsQuery {
+    /// Server of the console, the default one when missing
+    server: Option<String>,
+}
+
+/// Command frame of the WebSocket console, `id` is echoed back in the reply
+#[derive(Deserialize)]
+struct WsCommand {
+    id: Option<String>,
+    #[serde(flatten)]
+    command: RconCommandRequest,
+}
+
+/// Frames sent by the WebSocket console
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum WsEvent<'a> {
+    /// `id` of the command frame and id of the RCON packet of the command
+    Response {
+        id: Option<&'a str>,
+        packet_id: i32,
+        output: &'a str,
+    },
+    Error {
+        id: Option<&'a str>,
+        error: &'static str,
+        message: String,
+    },
+    State {
+        connection: ConnectionState,
+    },
+}
+
+impl WsEvent<'_> {
+    fn to_text(&self) -> String {
+        serde_json::to_string(self).expect("events are always serializable")
     }
 }
 
+/// Live console: JSON command frames in, responses and connection state changes out.
+/// Commands go through the same permission checks and audit log of `/rcon/command`, the
+/// token or session is checked again before each one.
+#[get("/rcon/ws")]
+#[allow(clippy::too_many_arguments)]
+async fn rcon_ws(
+    request: HttpRequest,
+    body: web::Payload,
+    caller: Caller,
+    query: web::Query<WsQuery>,
+    registry: web::Data<ServerRegistry>,
+    user_manager: web::Data<UserManager>,
+    sessions: web::Data<SqliteSessionStore>,
+    cookie_key: web::Data<Key>,
+    audit: web::Data<AuditLog>,
+    origins: web::Data<AllowedOrigins>,
+) -> CrateResult<impl Responder> {
+    origins.check(&request)?;
+    let server = query.into_inner().server.unwrap_or_else(|| DEFAULT_SERVER.to_string());
+    let states = registry.get(&server).await?.subscribe();
+    
+    let (response, session, messages) =
+        actix_ws::handle(&request, body).map_err(Error::server_error)?;
+    actix_web::rt::spawn(async move {
+        let console = WsConsole {
+            request, caller, server, registry, user_manager, sessions, cookie_key, audit,
+        };
+        console.run(session, messages, states).await;
+    });
+    
+    Ok(response)
+}
+
+/// `Origin`s from [`HttpConfig::allowed_origins`]
+struct AllowedOrigins(Vec<String>);
+
+impl AllowedOrigins {
+    /// Browsers send the cookies of this server with WebSockets opened by any site, so the
+    /// console only accepts the pages of this server and the allowed origins. Clients
+    /// without an `Origin` aren't browsers and can't be hijacked.
+    fn check(&self, request: &HttpRequest) -> CrateResult<()> {
+        let Some(origin) = request.headers().get(header::ORIGIN) else {
+            return Ok(());
+        };
+        let origin = origin.to_str().unwrap_or_default();
+        let same_host = origin
+            .split_once("://")
+            .is_some_and(|(_, host)| host == request.connection_info().host());
+        if same_host || self.0.iter().any(|allowed| allowed == origin) {
+            Ok(())
+        } else {
+            Err(Error::dont_have_permission(format!("origin {origin:?} is not allowed")))
+        }
+    }
+}
+
+struct WsConsole {
+    request: HttpRequest,
+    /// Caller of the upgrade request
+    caller: Caller,
+    server: String,
+    registry: web::Data<ServerRegistry>,
+    user_manager: web::Data<UserManager>,
+    sessions: web::Data<SqliteSessionStore>,
+    cookie_key: web::Data<Key>,
+    audit: web::Data<AuditLog>,
+}
+
+impl WsConsole {
+    async fn run(
+        &self,
+        mut session: actix_ws::Session,
+        mut messages: actix_ws::MessageStream,
+        mut states: watch::Receiver<ConnectionState>,
+    ) {
+        let state = WsEvent::State { connection: states.borrow_and_update().clone() };
+        if session.text(state.to_text()).await.is_err() {
+            return;
+        }
+        
+        loop {
+            let reply = tokio::select! {
+                changed = states.changed() => match changed {
+                    Ok(()) => {
+                        WsEvent::State { connection: states.borrow_and_update().clone() }.to_text()
+                    },
+                    // the server was replaced in the registry
+                    Err(_) => break,
+                },
+                message = messages.recv() => match message {
+                    Some(Ok(actix_ws::Message::Text(text))) => match self.verify_caller().await {
+                        Ok(caller) => self.exec(caller, &text).await,
+                        // revoked token, deleted session or disabled user
+                        Err(err) => {
+                            let message = err.to_string();
+                            let error = WsEvent::Error { id: None, error: err.kind(), message };
+                            let _ = session.text(error.to_text()).await;
+                            break;
+                        },
+                    },
+                    Some(Ok(actix_ws::Message::Ping(bytes))) => {
+                        if session.pong(&bytes).await.is_err() {
+                            return;
+                        }
+                        continue;
+                    },
+                    Some(Ok(actix_ws::Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                },
+            };
+            
+            if session.text(reply).await.is_err() {
+                return;
+            }
+        }
+        
+        let _ = session.close(None).await;
+    }
+    
+    /// Authenticates the upgrade request again, the socket outlives the token or session
+    /// it was opened with
+    async fn verify_caller(&self) -> CrateResult<Caller> {
+        if self.caller.is_token() {
+            return Caller::extract(&self.request).await;
+        }
+        
+        // the session of the request is only readable while the upgrade is handled
+        let nick = self.sessions.session_user(&self.request, &self.cookie_key).await?;
+        if nick.as_ref() != Some(&self.caller.nick) {
+            return Err(Error::not_logged_in("session closed"));
+        }
+        self.user_manager.ensure_active(&self.caller.nick).await?;
+        Ok(self.caller.clone())
+    }
+    
+    async fn exec(&self, caller: Caller, text: &str) -> String {
+        let frame: WsCommand = match serde_json::from_str(text) {
+            Ok(frame) => frame,
+            Err(err) => {
+                let message = err.to_string();
+                return WsEvent::Error { id: None, error: "invalid_frame", message }.to_text();
+            },
+        };
+        
+        let result = exec_rcon_command(
+            &self.request,
+            Ok(caller),
+            &self.server,
+            &frame.command,
+            &self.registry,
+            &self.user_manager,
+            &self.audit,
+        ).await;
+        
+        let id = frame.id.as_deref();
+        match result {
+            Ok(response) => {
+                let packet_id = response.request_id;
+                WsEvent::Response { id, packet_id, output: &response.output }.to_text()
+            },
+            Err(err) => {
+                WsEvent::Error { id, error: err.kind(), message: err.to_string() }.to_text()
+            },
+        }
+    }
+}
+
+#[get("/rcon/status")]
+async fn rcon_status(
+    _caller: Caller,
+    registry: web::Data<ServerRegistry>,
+) -> CrateResult<impl Responder> {
+    Ok(web::Json(registry.get(DEFAULT_SERVER).await?.state()))
+}
+
+#[get("/servers/{name}/rcon/status")]
+async fn server_rcon_status(
+    _caller: Caller,
+    server: web::Path<String>,
+    registry: web::Data<ServerRegistry>,
+) -> CrateResult<impl Responder> {
+    Ok(web::Json(registry.get(&server).await?.state()))
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+enum StatKind {
+    Basic,
+    #[default]
+    Full,
+    /// Server List Ping on the game port, for servers without `enable-query`
+    Ping,
+}
+
+#[derive(Deserialize)]
+struct StatusQuery {
+    #[serde(default)]
+    stat: StatKind,
+}
+
+/// Public status of the server from the Query protocol or the Server List Ping,
+/// no login needed
+#[get("/servers/{name}/status")]
+async fn server_status(
+    server: web::Path<String>,
+    query: web::Query<StatusQuery>,
+    registry: web::Data<ServerRegistry>,
+) -> CrateResult<impl Responder> {
+    let target = registry.target(&server).await?;
+    
+    let response = match query.stat {
+        StatKind::Basic => {
+            let client = QueryClient::connect(&target.query_addr()).await?;
+            HttpResponse::Ok().json(client.basic_stat().await?)
+        },
+        StatKind::Full => {
+            let client = QueryClient::connect(&target.query_addr()).await?;
+            HttpResponse::Ok().json(client.full_stat().await?)
+        },
+        StatKind::Ping => {
+            HttpResponse::Ok().json(ping::ping(&target.host, target.game_port()).await?)
+        },
+    };
+    Ok(response)
+}
+
+#[get("/servers")]
+async fn list_servers(
+    _caller: Caller,
+    registry: web::Data<ServerRegistry>,
+) -> CrateResult<impl Responder> {
+    Ok(web::Json(registry.list().await))
+}
+
+#[post("/servers")]
+async fn add_server(
+    caller: Caller,
+    target: web::Json<RconTarget>,
+    registry: web::Data<ServerRegistry>,
+    user_manager: web::Data<UserManager>,
+) -> CrateResult<impl Responder> {
+    caller.require_admin(&user_manager).await?;
+    
+    registry.add_server(target.into_inner()).await?;
+    
+    Ok(HttpResponse::Created())
+}
+
 #[derive(Deserialize, Serialize)]
 struct CreateUserRequest {
     nick: String,
@@ -160,52 +611,382 @@ struct CreateUserRequest {
 
 #[post("/user/new")]
 async fn create_user(
-    user: Option<Identity>, 
+    caller: Caller,
     command: web::Json<CreateUserRequest>,
     user_manager: web::Data<UserManager>,
     pass_manager: web::Data<PasswordManager>,
-) -> impl Responder {
-    let _ = user.expect("logged user");
+) -> CrateResult<impl Responder> {
+    caller.require_admin(&user_manager).await?;
     
-    let user_hash = pass_manager.hash_password(command.password.clone()).unwrap();
-    match user_manager.new_user(
+    let user_hash = pass_manager
+        .hash_password(command.password.clone())?;
+    user_manager.new_user(
         command.nick.clone(), 
         user_hash.clone(),
-    ).await {
-        Ok(_) => {
-            HttpResponse::Created()
-        },
-        Err(err) => {
-            println!("{}", err);
-            HttpResponse::InternalServerError()
-        }
-    }
+    ).await?;
+    
+    Ok(HttpResponse::Created())
 }
 
 
 #[derive(Deserialize, Serialize)]
 struct GrantUserPermissionsRequest {
     nick: String,
+    /// Server where the permissions apply, every server when missing
+    server: Option<String>,
     permissions: Vec<String>,
 }
 
 #[post("/user/grant/permission")]
 async fn add_permissions(
-    user: Option<Identity>, 
+    caller: Caller,
     command: web::Json<GrantUserPermissionsRequest>,
     user_manager: web::Data<UserManager>,
-) -> impl Responder {
-    let identity = user.expect("logged user");
+) -> CrateResult<impl Responder> {
+    caller.require_admin(&user_manager).await?;
     
-    let requirer_nick = identity.id().unwrap();
-    if let Ok(_) = user_manager.has_permissions(requirer_nick.clone(), "admin".to_string()).await {
-        user_manager
-            .add_user_permissions(command.nick.clone(), command.permissions.clone())
-            .await
-            .expect("can't create permission for user");
-        
-        HttpResponse::Ok()
-    } else {
-        HttpResponse::Unauthorized()
+    let server = command.server.clone().unwrap_or_else(|| ALL_SERVERS.to_string());
+    user_manager
+        .add_user_permissions(command.nick.clone(), server, command.permissions.clone())
+        .await?;
+    
+    Ok(HttpResponse::Ok())
+}
+
+
+#[derive(Deserialize)]
+struct ServerScope {
+    /// Server of the grant, the grant for every server when missing
+    server: Option<String>,
+}
+
+#[delete("/user/{nick}/permission/{command}")]
+async fn revoke_permission(
+    caller: Caller,
+    path: web::Path<(String, String)>,
+    scope: web::Query<ServerScope>,
+    user_manager: web::Data<UserManager>,
+) -> CrateResult<impl Responder> {
+    caller.require_admin(&user_manager).await?;
+    
+    let (nick, command) = path.into_inner();
+    let server = scope.into_inner().server.unwrap_or_else(|| ALL_SERVERS.to_string());
+    user_manager.revoke_user_permission(nick, server, command).await?;
+    
+    Ok(HttpResponse::NoContent())
+}
+
+#[delete("/user/{nick}")]
+async fn delete_user(
+    caller: Caller,
+    nick: web::Path<String>,
+    user_manager: web::Data<UserManager>,
+    sessions: web::Data<SqliteSessionStore>,
+) -> CrateResult<impl Responder> {
+    caller.require_admin(&user_manager).await?;
+    
+    let nick = nick.into_inner();
+    user_manager.delete_user(nick.clone()).await?;
+    sessions.delete_user_sessions(&nick).await?;
+    
+    Ok(HttpResponse::NoContent())
+}
+
+#[get("/users")]
+async fn list_users(
+    caller: Caller,
+    user_manager: web::Data<UserManager>,
+) -> CrateResult<impl Responder> {
+    caller.require_admin(&user_manager).await?;
+    
+    Ok(web::Json(user_manager.list_users().await?))
+}
+
+#[get("/user/{nick}/permissions")]
+async fn user_permissions(
+    caller: Caller,
+    nick: web::Path<String>,
+    user_manager: web::Data<UserManager>,
+) -> CrateResult<impl Responder> {
+    // users can always see their own permissions
+    if caller.nick != *nick {
+        caller.require_admin(&user_manager).await?;
     }
+    
+    Ok(web::Json(user_manager.grants(&nick).await?))
+}
+
+#[post("/user/{nick}/disable")]
+async fn disable_user(
+    caller: Caller,
+    nick: web::Path<String>,
+    user_manager: web::Data<UserManager>,
+    sessions: web::Data<SqliteSessionStore>,
+) -> CrateResult<impl Responder> {
+    caller.require_admin(&user_manager).await?;
+    
+    let nick = nick.into_inner();
+    user_manager.set_disabled(nick.clone(), true).await?;
+    sessions.delete_user_sessions(&nick).await?;
+    
+    Ok(HttpResponse::NoContent())
+}
+
+#[post("/user/{nick}/enable")]
+async fn enable_user(
+    caller: Caller,
+    nick: web::Path<String>,
+    user_manager: web::Data<UserManager>,
+) -> CrateResult<impl Responder> {
+    caller.require_admin(&user_manager).await?;
+    
+    user_manager.set_disabled(nick.into_inner(), false).await?;
+    
+    Ok(HttpResponse::NoContent())
+}
+
+#[derive(Deserialize, Serialize)]
+struct CreateRoleRequest {
+    name: String,
+    /// Server where the permissions apply, every server when missing
+    server: Option<String>,
+    permissions: Vec<String>,
+}
+
+#[post("/roles")]
+async fn create_role(
+    caller: Caller,
+    command: web::Json<CreateRoleRequest>,
+    user_manager: web::Data<UserManager>,
+) -> CrateResult<impl Responder> {
+    caller.require_admin(&user_manager).await?;
+    
+    let server = command.server.clone().unwrap_or_else(|| ALL_SERVERS.to_string());
+    user_manager
+        .add_role_permissions(command.name.clone(), server, command.permissions.clone())
+        .await?;
+    
+    Ok(HttpResponse::Created())
+}
+
+#[get("/roles")]
+async fn list_roles(
+    caller: Caller,
+    user_manager: web::Data<UserManager>,
+) -> CrateResult<impl Responder> {
+    caller.require_admin(&user_manager).await?;
+    
+    Ok(web::Json(user_manager.list_roles().await?))
+}
+
+#[derive(Deserialize, Serialize)]
+struct AssignRolesRequest {
+    roles: Vec<String>,
+}
+
+#[post("/user/{nick}/roles")]
+async fn assign_roles(
+    caller: Caller,
+    nick: web::Path<String>,
+    command: web::Json<AssignRolesRequest>,
+    user_manager: web::Data<UserManager>,
+) -> CrateResult<impl Responder> {
+    caller.require_admin(&user_manager).await?;
+    
+    user_manager.assign_roles(nick.into_inner(), command.roles.clone()).await?;
+    
+    Ok(HttpResponse::Ok())
+}
+
+#[delete("/user/{nick}/roles/{role}")]
+async fn unassign_role(
+    caller: Caller,
+    path: web::Path<(String, String)>,
+    user_manager: web::Data<UserManager>,
+) -> CrateResult<impl Responder> {
+    caller.require_admin(&user_manager).await?;
+    
+    let (nick, role) = path.into_inner();
+    user_manager.unassign_role(nick, role).await?;
+    
+    Ok(HttpResponse::NoContent())
+}
+
+#[get("/user/{nick}/roles")]
+async fn user_roles(
+    caller: Caller,
+    nick: web::Path<String>,
+    user_manager: web::Data<UserManager>,
+) -> CrateResult<impl Responder> {
+    // users can always see their own roles
+    if caller.nick != *nick {
+        caller.require_admin(&user_manager).await?;
+    }
+    
+    Ok(web::Json(user_manager.user_roles(nick.into_inner()).await?))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ExportFormat {
+    Json,
+    Csv,
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    format: Option<ExportFormat>,
+}
+
+#[get("/audit")]
+async fn audit_log(
+    caller: Caller,
+    filter: web::Query<AuditFilter>,
+    export: web::Query<ExportQuery>,
+    user_manager: web::Data<UserManager>,
+    audit: web::Data<AuditLog>,
+) -> CrateResult<impl Responder> {
+    caller.require_admin(&user_manager).await?;
+    
+    let page = audit.query(&filter).await?;
+    
+    let response = match export.format {
+        Some(ExportFormat::Csv) => {
+            let mut response = HttpResponse::Ok();
+            response.content_type("text/csv");
+            if let Some(cursor) = page.next_cursor {
+                response.insert_header(("X-Next-Cursor", cursor.to_string()));
+            }
+            response.body(page.to_csv())
+        },
+        Some(ExportFormat::Json) | None => HttpResponse::Ok().json(page),
+    };
+    
+    Ok(response)
+}
+
+#[derive(Deserialize, Serialize)]
+struct TokenScopeRequest {
+    command: String,
+    /// Server where the pattern applies, every server when missing
+    server: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct CreateTokenRequest {
+    name: String,
+    /// Permission patterns allowed to the token, each one within a grant of the user
+    scopes: Vec<TokenScopeRequest>,
+    expires_in_days: Option<u32>,
+}
+
+const DEFAULT_TOKEN_DAYS: u32 = 30;
+const MAX_TOKEN_DAYS: u32 = 365;
+
+#[post("/tokens")]
+async fn create_token(
+    caller: Caller,
+    command: web::Json<CreateTokenRequest>,
+    user_manager: web::Data<UserManager>,
+    token_manager: web::Data<TokenManager>,
+) -> CrateResult<impl Responder> {
+    // a token could otherwise extend its own lifetime
+    if caller.is_token() {
+        return Err(Error::dont_have_permission("tokens are created from a login session"));
+    }
+    
+    let scopes: Vec<Grant> = command
+        .scopes
+        .iter()
+        .map(|scope| Grant {
+            command: scope.command.clone(),
+            server: scope.server.clone().unwrap_or_else(|| ALL_SERVERS.to_string()),
+        })
+        .collect();
+    let grants = user_manager.grants(&caller.nick).await?;
+    let not_granted = scopes
+        .iter()
+        .find(|scope| !grants.iter().any(|grant| grant.covers(scope, &caller.nick)));
+    if let Some(scope) = not_granted {
+        return Err(Error::dont_have_permission(format!(
+            "{} can't grant {} on {}", caller.nick, scope.command, scope.server)));
+    }
+    
+    let days = command.expires_in_days.unwrap_or(DEFAULT_TOKEN_DAYS).clamp(1, MAX_TOKEN_DAYS);
+    let token = token_manager
+        .create_token(
+            caller.nick,
+            command.name.clone(),
+            scopes,
+            days as i64 * 24 * 60 * 60,
+        )
+        .await?;
+    
+    Ok(HttpResponse::Created().json(token))
+}
+
+#[get("/tokens")]
+async fn list_tokens(
+    caller: Caller,
+    token_manager: web::Data<TokenManager>,
+) -> CrateResult<impl Responder> {
+    Ok(web::Json(token_manager.list_tokens(&caller.nick).await?))
+}
+
+#[delete("/tokens/{id}")]
+async fn revoke_token(
+    caller: Caller,
+    id: web::Path<String>,
+    user_manager: web::Data<UserManager>,
+    token_manager: web::Data<TokenManager>,
+) -> CrateResult<impl Responder> {
+    // admins can revoke the tokens of anyone
+    let owner = match caller.require_admin(&user_manager).await {
+        Ok(_) => None,
+        Err(_) => Some(caller.nick.as_str()),
+    };
+    token_manager.revoke(&id, owner).await?;
+    
+    Ok(HttpResponse::NoContent())
+}
+
+#[get("/user/{nick}/sessions")]
+async fn list_sessions(
+    caller: Caller,
+    nick: web::Path<String>,
+    user_manager: web::Data<UserManager>,
+    sessions: web::Data<SqliteSessionStore>,
+) -> CrateResult<impl Responder> {
+    caller.require_admin(&user_manager).await?;
+    
+    Ok(web::Json(sessions.list_sessions(&nick).await?))
+}
+
+#[delete("/user/{nick}/sessions")]
+async fn kill_user_sessions(
+    caller: Caller,
+    nick: web::Path<String>,
+    user_manager: web::Data<UserManager>,
+    sessions: web::Data<SqliteSessionStore>,
+) -> CrateResult<impl Responder> {
+    caller.require_admin(&user_manager).await?;
+    
+    sessions.delete_user_sessions(&nick).await?;
+    
+    Ok(HttpResponse::NoContent())
+}
+
+#[delete("/user/{nick}/sessions/{id}")]
+async fn kill_session(
+    caller: Caller,
+    path: web::Path<(String, i64)>,
+    user_manager: web::Data<UserManager>,
+    sessions: web::Data<SqliteSessionStore>,
+) -> CrateResult<impl Responder> {
+    caller.require_admin(&user_manager).await?;
+    
+    let (nick, id) = path.into_inner();
+    sessions.delete_session(&nick, id).await?;
+    
+    Ok(HttpResponse::NoContent())
 }
@@ -0,0 +1,360 @@
+'use strict';
+
+const $ = (selector) => document.querySelector(selector);
+
+const state = {
+  me: null,
+  socket: null,
+  auditFilter: null,
+  auditCursor: null,
+  nextFrame: 1,
+};
+
+async function api(method, path, body) {
+  const options = { method, headers: {} };
+  if (body !== undefined) {
+    options.headers['Content-Type'] = 'application/json';
+    options.body = JSON.stringify(body);
+  }
+
+  const response = await fetch(path, options);
+  const text = await response.text();
+  const data = text ? JSON.parse(text) : null;
+  if (!response.ok) {
+    throw new Error(data && data.message ? data.message : `${method} ${path}: ${response.status}`);
+  }
+  return data;
+}
+
+function showError(err) {
+  const error = $('#error');
+  error.textContent = err ? err.message : '';
+  error.hidden = !err;
+}
+
+function element(tag, text, className) {
+  const el = document.createElement(tag);
+  if (text !== undefined) el.textContent = text;
+  if (className) el.className = className;
+  return el;
+}
+
+function button(text, onClick) {
+  const el = element('button', text);
+  el.addEventListener('click', () => onClick().catch(showError));
+  return el;
+}
+
+// Same rules of src/permission.rs
+function tokens(line) {
+  return line.trim().replace(/^\/+/, '').split(/\s+/).filter(Boolean);
+}
+
+function tokenMatches(expected, actual, nick) {
+  if (expected === '*') return true;
+  if (expected === '{self}') return actual.toLowerCase() === nick.toLowerCase();
+  return expected === actual;
+}
+
+function patternMatches(pattern, line, nick) {
+  const expected = tokens(pattern);
+  const actual = tokens(line);
+  if (!expected.length || !actual.length || !tokenMatches(expected[0], actual[0], nick)) {
+    return false;
+  }
+  if (expected.length === 1) return true;
+
+  for (let i = 1; i < expected.length; i++) {
+    if (actual[i] === undefined) return false;
+    if (expected[i] === '*' && i === expected.length - 1) return true;
+    if (!tokenMatches(expected[i], actual[i], nick)) return false;
+  }
+  return expected.length === actual.length;
+}
+
+function stripColors(text) {
+  return text.replace(/§./g, '');
+}
+
+function showSection(name) {
+  for (const section of document.querySelectorAll('section')) {
+    section.hidden = section.id !== name;
+  }
+  for (const tab of document.querySelectorAll('#tabs button[data-tab]')) {
+    tab.classList.toggle('active', tab.dataset.tab === name);
+  }
+}
+
+async function start() {
+  try {
+    state.me = await api('GET', '/me');
+  } catch (err) {
+    state.me = null;
+    $('#tabs').hidden = true;
+    showSection('login');
+    return;
+  }
+
+  $('#tabs').hidden = false;
+  $('#whoami').textContent = state.me.nick;
+  for (const tab of document.querySelectorAll('#tabs [data-admin]')) {
+    tab.hidden = !state.me.admin;
+  }
+  showSection('console');
+  await loadServers();
+}
+
+// Console
+
+function allowedPatterns() {
+  const server = $('#server').value;
+  return state.me.permissions
+    .filter((grant) => grant.server === '*' || grant.server === server)
+    .map((grant) => grant.command)
+    .filter((command) => command !== 'admin');
+}
+
+function isAllowed(line) {
+  return allowedPatterns().some((pattern) => patternMatches(pattern, line, state.me.nick));
+}
+
+function print(text, className) {
+  const output = $('#output');
+  output.append(element('div', text, className));
+  output.scrollTop = output.scrollHeight;
+}
+
+function renderAllowed() {
+  const allowed = $('#allowed');
+  allowed.replaceChildren();
+  for (const pattern of allowedPatterns()) {
+    const template = pattern.replaceAll('{self}', state.me.nick).replace(/\s\*$/, ' ');
+    allowed.append(button(pattern, async () => {
+      $('#command').value = template;
+      $('#command').focus();
+      updateSend();
+    }));
+  }
+  if (!allowed.children.length) {
+    allowed.append(element('p', 'You are not allowed to run commands on this server.', 'hint'));
+  }
+}
+
+function updateSend() {
+  const line = $('#command').value;
+  $('#send').disabled = !line.trim() || !isAllowed(line)
+    || !state.socket || state.socket.readyState !== WebSocket.OPEN;
+}
+
+function openSocket() {
+  if (state.socket) state.socket.close();
+
+  const scheme = location.protocol === 'https:' ? 'wss' : 'ws';
+  const server = encodeURIComponent($('#server').value);
+  const socket = new WebSocket(`${scheme}://${location.host}/rcon/ws?server=${server}`);
+  state.socket = socket;
+
+  socket.addEventListener('open', updateSend);
+  socket.addEventListener('close', () => {
+    if (state.socket === socket) $('#state').textContent = 'disconnected';
+    updateSend();
+  });
+  socket.addEventListener('message', (event) => {
+    const frame = JSON.parse(event.data);
+    if (frame.type === 'state') {
+      const connection = frame.connection;
+      $('#state').textContent = connection.state === 'connected'
+        ? 'connected'
+        : `reconnecting (attempt ${connection.attempt}): ${connection.last_error}`;
+    } else if (frame.type === 'response') {
+      print(stripColors(frame.output) || '(no output)');
+    } else if (frame.type === 'error') {
+      print(frame.message, 'failed');
+    }
+  });
+}
+
+async function loadServers() {
+  const servers = await api('GET', '/servers');
+  const select = $('#server');
+  select.replaceChildren(...servers.map((server) => element('option', server.name)));
+  renderAllowed();
+  if (servers.length) openSocket();
+}
+
+function sendCommand(event) {
+  event.preventDefault();
+  const line = $('#command').value.trim();
+  if (!isAllowed(line)) return;
+
+  const [command, ...args] = tokens(line);
+  state.socket.send(JSON.stringify({ id: String(state.nextFrame++), command, args }));
+  print(`> /${[command, ...args].join(' ')}`, 'sent');
+  $('#command').value = '';
+  updateSend();
+}
+
+// Users
+
+async function loadUsers() {
+  const users = await api('GET', '/users');
+  const rows = users.map((user) => {
+    const row = element('tr');
+    row.append(element('td', user.nick), element('td', user.disabled ? 'disabled' : 'active'));
+
+    const actions = element('td', undefined, 'actions');
+    const nick = encodeURIComponent(user.nick);
+    actions.append(
+      button('Permissions', () => loadPermissions(user.nick)),
+      user.disabled
+        ? button('Enable', () => api('POST', `/user/${nick}/enable`).then(loadUsers))
+        : button('Disable', () => api('POST', `/user/${nick}/disable`).then(loadUsers)),
+      button('Delete', async () => {
+        if (!confirm(`Delete ${user.nick}?`)) return;
+        await api('DELETE', `/user/${nick}`);
+        $('#permissions').hidden = true;
+        await loadUsers();
+      }),
+    );
+    row.append(actions);
+    return row;
+  });
+  $('#users').replaceChildren(...rows);
+}
+
+async function loadPermissions(nick) {
+  const grants = await api('GET', `/user/${encodeURIComponent(nick)}/permissions`);
+  $('#permissions-nick').textContent = nick;
+  $('#permissions').hidden = false;
+
+  const rows = grants.map((grant) => {
+    const row = element('tr');
+    row.append(element('td', grant.server), element('td', grant.command));
+
+    const actions = element('td', undefined, 'actions');
+    actions.append(button('Revoke', async () => {
+      const path = `/user/${encodeURIComponent(nick)}/permission/${encodeURIComponent(grant.command)}`;
+      await api('DELETE', `${path}?server=${encodeURIComponent(grant.server)}`);
+      await loadPermissions(nick);
+    }));
+    row.append(actions);
+    return row;
+  });
+  $('#grants').replaceChildren(...rows);
+}
+
+async function createUser(event) {
+  event.preventDefault();
+  const form = event.target;
+  await api('POST', '/user/new', { nick: form.nick.value, password: form.password.value });
+  form.reset();
+  await loadUsers();
+}
+
+async function grantPermissions(event) {
+  event.preventDefault();
+  const form = event.target;
+  const permissions = form.permissions.value.split(',').map((p) => p.trim()).filter(Boolean);
+  await api('POST', '/user/grant/permission', {
+    nick: form.nick.value,
+    server: form.server.value.trim() || null,
+    permissions,
+  });
+  await loadPermissions(form.nick.value);
+  form.permissions.value = '';
+}
+
+// Audit
+
+function auditQuery() {
+  const form = $('#audit-form');
+  const params = new URLSearchParams();
+  for (const name of ['nick', 'command', 'allowed']) {
+    if (form[name].value) params.set(name, form[name].value);
+  }
+  for (const name of ['from', 'to']) {
+    if (form[name].value) params.set(name, Math.floor(new Date(form[name].value) / 1000));
+  }
+  return params;
+}
+
+async function loadAudit(append) {
+  const params = new URLSearchParams(state.auditFilter);
+  if (append && state.auditCursor) params.set('cursor', state.auditCursor);
+
+  const page = await api('GET', `/audit?${params}`);
+  const rows = page.entries.map((entry) => {
+    const row = element('tr');
+    row.append(
+      element('td', new Date(entry.created_at * 1000).toLocaleString()),
+      element('td', entry.user_nick || '-'),
+      element('td', entry.server),
+      element('td', `/${[entry.command, ...entry.args].join(' ')}`),
+      element('td', entry.allowed ? 'yes' : 'no'),
+      element('td', entry.error || stripColors(entry.response || '')),
+    );
+    return row;
+  });
+
+  if (append) {
+    $('#entries').append(...rows);
+  } else {
+    $('#entries').replaceChildren(...rows);
+  }
+  state.auditCursor = page.next_cursor;
+  $('#audit-next').hidden = page.next_cursor === null;
+}
+
+function searchAudit(event) {
+  event.preventDefault();
+  state.auditFilter = auditQuery();
+  const csv = new URLSearchParams(state.auditFilter);
+  csv.set('format', 'csv');
+  $('#audit-csv').href = `/audit?${csv}`;
+  loadAudit(false).catch(showError);
+}
+
+// Wiring
+
+function handle(handler) {
+  return (event) => {
+    showError(null);
+    Promise.resolve(handler(event)).catch(showError);
+  };
+}
+
+$('#login-form').addEventListener('submit', handle(async (event) => {
+  event.preventDefault();
+  const form = event.target;
+  await api('POST', '/login', { user: form.user.value, password: form.password.value });
+  form.reset();
+  await start();
+}));
+
+$('#logout').addEventListener('click', handle(async () => {
+  if (state.socket) state.socket.close();
+  state.socket = null;
+  await api('POST', '/logout');
+  await start();
+}));
+
+for (const tab of document.querySelectorAll('#tabs button[data-tab]')) {
+  tab.addEventListener('click', handle(async () => {
+    showSection(tab.dataset.tab);
+    if (tab.dataset.tab === 'admin') await loadUsers();
+    if (tab.dataset.tab === 'audit') searchAudit(new Event('submit'));
+  }));
+}
+
+$('#server').addEventListener('change', () => {
+  renderAllowed();
+  openSocket();
+});
+$('#command').addEventListener('input', updateSend);
+$('#command-form').addEventListener('submit', sendCommand);
+$('#user-form').addEventListener('submit', handle(createUser));
+$('#grant-form').addEventListener('submit', handle(grantPermissions));
+$('#audit-form').addEventListener('submit', handle(searchAudit));
+$('#audit-next').addEventListener('click', handle(() => loadAudit(true)));
+
+start().catch(showError);